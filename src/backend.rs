@@ -0,0 +1,169 @@
+//! The two ways the game can be drawn: the default graphical window, which uses the tileset in
+//! `resources/`, and the `terminal` feature, which draws characters straight to the text
+//! terminal so the game can be played over SSH. The rest of the game draws tileset glyphs onto
+//! three layered consoles and goes through this module for the parts that differ.
+
+use super::settings::Settings;
+use rltk::{BResult, BTerm, BTermBuilder, FontCharType, Rltk};
+
+#[cfg(all(feature = "opengl", feature = "terminal"))]
+compile_error!(
+    "The opengl and terminal features can't be used together; build the terminal version with `--no-default-features --features terminal`."
+);
+
+/// The console the map and entities are drawn on.
+pub const MAP_LAYER: usize = 0;
+/// The console between the map and the text, which only the minimap is drawn on.
+pub const OVERLAY_LAYER: usize = 1;
+/// The console text and menus are drawn on, above everything else.
+pub const TEXT_LAYER: usize = 2;
+/// How many cells across and down every console is.
+pub const SCREEN_WIDTH: i32 = 80;
+pub const SCREEN_HEIGHT: i32 = 50;
+
+#[cfg(not(feature = "terminal"))]
+pub fn build_context(settings: &Settings) -> BResult<Rltk> {
+    let mut context = BTermBuilder::new()
+        .with_title("RogueSpace")
+        .with_fps_cap(settings.fps_cap as f32)
+        .with_dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .with_tile_dimensions(settings.tile_size, settings.tile_size)
+        .with_resource_path("resources/")
+        .with_font("monochrome-transparent_packed.png", 16, 16)
+        .with_font("terminal8x8.jpg", 8, 8)
+        .with_simple_console(
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            "monochrome-transparent_packed.png",
+        )
+        .with_simple_console_no_bg(
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            "monochrome-transparent_packed.png",
+        )
+        .with_simple_console_no_bg(SCREEN_WIDTH, SCREEN_HEIGHT, "terminal8x8.jpg")
+        .build()?;
+    apply_settings(&mut context, settings);
+    Ok(context)
+}
+
+/// The terminal only has the one layer, since each console is redrawn in full over the one
+/// below it, blanks included. Everything is drawn onto it in order instead.
+#[cfg(feature = "terminal")]
+pub fn build_context(settings: &Settings) -> BResult<Rltk> {
+    BTermBuilder::new()
+        .with_title("RogueSpace")
+        .with_fps_cap(settings.fps_cap as f32)
+        .with_dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .with_tile_dimensions(8, 8)
+        .with_font("terminal8x8.jpg", 8, 8)
+        .with_simple_console(SCREEN_WIDTH, SCREEN_HEIGHT, "terminal8x8.jpg")
+        .build()
+}
+
+/// Applies the display settings that can change while the game is running. The terminal has no
+/// scanlines to turn on or off.
+pub fn apply_settings(ctx: &mut Rltk, settings: &Settings) {
+    #[cfg(not(feature = "terminal"))]
+    {
+        ctx.post_scanlines = settings.scanlines;
+        ctx.post_screenburn = settings.scanlines;
+    }
+    #[cfg(feature = "terminal")]
+    let _ = (ctx, settings);
+}
+
+/// Makes `layer` the console that's drawn to.
+pub fn set_layer(ctx: &mut Rltk, layer: usize) {
+    if cfg!(feature = "terminal") {
+        BTerm::set_active_console(ctx, MAP_LAYER);
+    } else {
+        BTerm::set_active_console(ctx, layer);
+    }
+}
+
+/// The glyph to draw for a theme's `glyph`, which indexes the tileset if `tileset` is set and
+/// code page 437 otherwise. The terminal can only show the latter.
+pub fn glyph(glyph: FontCharType, tileset: bool) -> FontCharType {
+    if cfg!(feature = "terminal") && tileset {
+        ascii_glyph(glyph)
+    } else {
+        glyph
+    }
+}
+
+/// Draws the active layer with the tileset, or with the code page 437 font if `tileset` is
+/// unset. The terminal always draws characters, so there's nothing to switch.
+pub fn use_tileset(ctx: &mut Rltk, tileset: bool) {
+    #[cfg(not(feature = "terminal"))]
+    ctx.set_active_font(if tileset { 0 } else { 1 }, false);
+    #[cfg(feature = "terminal")]
+    let _ = (ctx, tileset);
+}
+
+/// The code page 437 character that stands in for tileset glyph `tile`, in the classic
+/// roguelike style, for tile themes drawn in the terminal. Anything without a stand-in is drawn
+/// as `?`.
+pub fn ascii_glyph(tile: FontCharType) -> FontCharType {
+    let character = match tile {
+        0 => 0,
+        // Map tiles
+        2 => 250, // ·
+        297 => b'>',
+        // Walls, by which neighbours are walls too
+        953 => 9,               // ○
+        904 | 806 | 855 => 186, // ║
+        950..=952 => 205,       // ═
+        957 => 188,             // ╝
+        810 => 187,             // ╗
+        1006 => 185,            // ╣
+        954 => 200,             // ╚
+        807 => 201,             // ╔
+        1003 => 204,            // ╠
+        958 => 202,             // ╩
+        811 => 203,             // ╦
+        860 => 206,             // ╬
+        // Creatures
+        25 => b'@',
+        127 => b'o',
+        123 => b'g',
+        75 => b'&',
+        // Items
+        669 => b'!',
+        673 => b'+',
+        768 => b'?',
+        427 | 429 => b'/',
+        720 => b'=',
+        330 | 378 => b')',
+        138 | 187 => b'[',
+        817 => b'%',
+        237 => b'$',
+        200 => 254, // ■
+        // Particles
+        617 => 176, // ░
+        529 => 3,   // ♥
+        575 => 19,  // ‼
+        _ => b'?',
+    };
+    character as FontCharType
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_glyph_in_use_has_a_stand_in() {
+        let tiles = [
+            2, 297, 953, 904, 806, 855, 952, 950, 951, 957, 810, 1006, 954, 807, 1003, 958, 811,
+            860, 25, 127, 123, 75, 669, 673, 427, 429, 720, 330, 378, 138, 187, 817, 237, 200, 617,
+            529, 575,
+        ];
+        for tile in tiles {
+            assert_ne!(ascii_glyph(tile), b'?' as FontCharType, "tile {}", tile);
+            assert!(ascii_glyph(tile) < 256);
+        }
+        assert_eq!(ascii_glyph(297), b'>' as FontCharType);
+        assert_eq!(ascii_glyph(1), b'?' as FontCharType);
+    }
+}
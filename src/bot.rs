@@ -0,0 +1,259 @@
+//! A scripted player for headless balance testing. It isn't clever: it heals and eats when it
+//! needs to, fights whatever it can see, grabs any loot it has spotted, explores the rest of the level
+//! and then heads for the stairs.
+
+use super::{
+    AreaOfEffect, Charges, CombatStats, Confusion, Equippable, Equipped, HungerClock, HungerState,
+    InBackpack, InflictsDamage, Item, LootContainer, Map, Monster, Position, ProvidesFood,
+    ProvidesHealing, Ranged, TileType, player::PlayerAction, settings::AutoPickup,
+};
+use rltk::{DijkstraMap, DistanceAlg, Point};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// How long the bot explores a level before giving up and looking for the way down.
+const EXPLORATION_TURNS: i32 = 800;
+
+/// The furthest the bot will plan a path.
+const MAX_PATH: f32 = 1024.0;
+
+pub struct Bot {
+    depth: i32,
+    turns_on_level: i32,
+    /// Items the bot has tried to pick up, so it doesn't keep trying after failing to.
+    abandoned: HashSet<Entity>,
+}
+
+impl Bot {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Bot {
+        Bot {
+            depth: 0,
+            turns_on_level: 0,
+            abandoned: HashSet::new(),
+        }
+    }
+
+    pub fn choose_action(&mut self, ecs: &World) -> PlayerAction {
+        let map = ecs.fetch::<Map>();
+        if map.depth != self.depth {
+            self.depth = map.depth;
+            self.turns_on_level = 0;
+        }
+        self.turns_on_level += 1;
+
+        let player_pos = *ecs.fetch::<Point>();
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+
+        self.look_after_self(ecs)
+            .or_else(|| fight(ecs, &map, player_pos))
+            .or_else(|| self.collect_loot(ecs, &map, player_pos))
+            .or_else(|| self.explore(&map, player_idx))
+            .or_else(|| descend(&map, player_idx))
+            .unwrap_or(PlayerAction::Wait)
+    }
+
+    /// Heals when badly hurt, eats when hungry and equips gear for empty slots.
+    fn look_after_self(&self, ecs: &World) -> Option<PlayerAction> {
+        let player_entity = *ecs.fetch::<Entity>();
+        let entities = ecs.entities();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let carried: Vec<Entity> = (&entities, &backpack)
+            .join()
+            .filter(|(_item, pack)| pack.owner == player_entity)
+            .map(|(item, _pack)| item)
+            .collect();
+        let use_item = |item: &Entity| PlayerAction::UseItem {
+            item: *item,
+            target: None,
+        };
+
+        let stats = ecs.read_storage::<CombatStats>();
+        if let Some(stats) = stats.get(player_entity)
+            && stats.hp * 2 < stats.max_hp
+        {
+            let healing = ecs.read_storage::<ProvidesHealing>();
+            if let Some(item) = carried.iter().find(|item| healing.get(**item).is_some()) {
+                return Some(use_item(item));
+            }
+        }
+
+        let hunger = ecs.read_storage::<HungerClock>();
+        if let Some(clock) = hunger.get(player_entity)
+            && matches!(clock.state, HungerState::Hungry | HungerState::Starving)
+        {
+            let food = ecs.read_storage::<ProvidesFood>();
+            if let Some(item) = carried.iter().find(|item| food.get(**item).is_some()) {
+                return Some(use_item(item));
+            }
+        }
+
+        let equippable = ecs.read_storage::<Equippable>();
+        let equipped = ecs.read_storage::<Equipped>();
+        let worn: Vec<_> = equipped
+            .join()
+            .filter(|worn| worn.owner == player_entity)
+            .map(|worn| worn.slot)
+            .collect();
+        carried
+            .iter()
+            .find(|item| {
+                equippable
+                    .get(**item)
+                    .is_some_and(|gear| !worn.contains(&gear.slot))
+            })
+            .map(use_item)
+    }
+
+    /// Picks up anything underfoot, and otherwise heads for the nearest loot it has seen.
+    fn collect_loot(&mut self, ecs: &World, map: &Map, player_pos: Point) -> Option<PlayerAction> {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let items = ecs.read_storage::<Item>();
+        let containers = ecs.read_storage::<LootContainer>();
+
+        let mut underfoot = Vec::new();
+        let mut goals = Vec::new();
+        for (entity, pos) in (&entities, &positions).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            if !map.revealed_tiles[idx] || self.abandoned.contains(&entity) {
+                continue;
+            }
+            if items.get(entity).is_some() {
+                if pos.x == player_pos.x && pos.y == player_pos.y {
+                    underfoot.push(entity);
+                } else {
+                    goals.push(idx);
+                }
+            } else if containers.get(entity).is_some() {
+                if is_adjacent(player_pos, pos) {
+                    return Some(step(player_pos, pos));
+                }
+                goals.push(idx);
+            }
+        }
+
+        if !underfoot.is_empty() {
+            // Anything still here next turn couldn't be picked up
+            self.abandoned.extend(underfoot);
+            return Some(PlayerAction::PickUp);
+        }
+        path_towards(map, map.xy_idx(player_pos.x, player_pos.y), &goals)
+    }
+
+    /// Heads for the nearest part of the level that hasn't been seen yet.
+    fn explore(&self, map: &Map, player_idx: usize) -> Option<PlayerAction> {
+        if self.turns_on_level > EXPLORATION_TURNS {
+            return None;
+        }
+        let unexplored: Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| !map.revealed_tiles[*idx] && map.tiles[*idx] != TileType::Wall)
+            .collect();
+        path_towards(map, player_idx, &unexplored)
+    }
+}
+
+/// Attacks the nearest visible monster, using a ranged device on it if one can reach.
+fn fight(ecs: &World, map: &Map, player_pos: Point) -> Option<PlayerAction> {
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let monsters = ecs.read_storage::<Monster>();
+
+    let mut visible: Vec<(f32, &Position)> = (&entities, &monsters, &positions)
+        .join()
+        .filter(|(_entity, _monster, pos)| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .map(|(_entity, _monster, pos)| {
+            let distance = DistanceAlg::Pythagoras.distance2d(player_pos, Point::new(pos.x, pos.y));
+            (distance, pos)
+        })
+        .collect();
+    visible.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (distance, target) = *visible.first()?;
+
+    if is_adjacent(player_pos, target) {
+        return Some(step(player_pos, target));
+    }
+    if let Some(item) = ranged_attack(ecs, distance) {
+        return Some(PlayerAction::UseItem {
+            item,
+            target: Some(Point::new(target.x, target.y)),
+        });
+    }
+    path_towards(
+        map,
+        map.xy_idx(player_pos.x, player_pos.y),
+        &[map.xy_idx(target.x, target.y)],
+    )
+}
+
+/// A charged device in the backpack that can hit a monster `distance` away without catching
+/// the player in its blast.
+fn ranged_attack(ecs: &World, distance: f32) -> Option<Entity> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let ranged = ecs.read_storage::<Ranged>();
+    let damages = ecs.read_storage::<InflictsDamage>();
+    let confuses = ecs.read_storage::<Confusion>();
+    let aoe = ecs.read_storage::<AreaOfEffect>();
+    let charges = ecs.read_storage::<Charges>();
+
+    (&entities, &backpack, &ranged)
+        .join()
+        .find(|(item, pack, ranged)| {
+            pack.owner == player_entity
+                && distance <= ranged.range as f32
+                && (damages.get(*item).is_some() || confuses.get(*item).is_some())
+                && aoe
+                    .get(*item)
+                    .is_none_or(|aoe| distance > (aoe.radius + 1) as f32)
+                && charges.get(*item).is_none_or(|charge| charge.current > 0)
+        })
+        .map(|(item, _pack, _ranged)| item)
+}
+
+/// Walks to the stairs once they've been seen, and takes them.
+fn descend(map: &Map, player_idx: usize) -> Option<PlayerAction> {
+    if map.tiles[player_idx] == TileType::DownStairs {
+        return Some(PlayerAction::Descend);
+    }
+    let stairs: Vec<usize> = (0..map.tiles.len())
+        .filter(|idx| map.revealed_tiles[*idx] && map.tiles[*idx] == TileType::DownStairs)
+        .collect();
+    path_towards(map, player_idx, &stairs)
+}
+
+fn is_adjacent(from: Point, to: &Position) -> bool {
+    i32::abs(from.x - to.x) <= 1 && i32::abs(from.y - to.y) <= 1
+}
+
+fn step(from: Point, to: &Position) -> PlayerAction {
+    PlayerAction::Move {
+        delta_x: to.x - from.x,
+        delta_y: to.y - from.y,
+        pick_up: AutoPickup::Off,
+    }
+}
+
+/// The first step along the shortest path to whichever of `goals` is closest, if any of them
+/// can be reached.
+fn path_towards(map: &Map, from: usize, goals: &[usize]) -> Option<PlayerAction> {
+    if goals.is_empty() {
+        return None;
+    }
+    let mut dijkstra = DijkstraMap::new(map.width, map.height, goals, map, MAX_PATH);
+    // The goals themselves are left at whatever distance the search wandered back to them with
+    for goal in goals.iter() {
+        dijkstra.map[*goal] = 0.0;
+    }
+    if dijkstra.map[from] == f32::MAX {
+        return None;
+    }
+    let next = DijkstraMap::find_lowest_exit(&dijkstra, from, map)?;
+    let width = map.width as usize;
+    Some(PlayerAction::Move {
+        delta_x: (next % width) as i32 - (from % width) as i32,
+        delta_y: (next / width) as i32 - (from / width) as i32,
+        pick_up: AutoPickup::Off,
+    })
+}
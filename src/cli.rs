@@ -0,0 +1,284 @@
+//! The command line: which mode the game starts in and what it starts with. Every mode but
+//! the game itself runs without opening a window, so scripts and tests can generate levels and
+//! play games too.
+
+use super::{
+    Map, State,
+    map::{MAP_HEIGHT, MAP_WIDTH, MIN_MAP_HEIGHT, MIN_MAP_WIDTH},
+    morgue, new_world, replay,
+    simulation::{self, GameSummary, SimulationOptions},
+};
+use specs::prelude::*;
+use std::io;
+use std::path::PathBuf;
+
+/// Every option, what goes after it and what it does.
+const FLAGS: &[(&str, &str, &str)] = &[
+    (
+        "--seed",
+        "<seed>",
+        "Starts the first new game from this seed",
+    ),
+    (
+        "--save-dir",
+        "<dir>",
+        "Keeps saves, settings, replays and morgue files in this directory",
+    ),
+    (
+        "--width",
+        "<tiles>",
+        "Generates levels this wide, from 40 to 80",
+    ),
+    (
+        "--height",
+        "<tiles>",
+        "Generates levels this tall, from 20 to 43",
+    ),
+    ("--wizard", "", "Turns on wizard mode and its debug console"),
+    ("--replay", "<file>", "Watches a replay"),
+    (
+        "--headless",
+        "",
+        "Plays the replay, or else a game by the bot, without a window and sums it up",
+    ),
+    (
+        "--max-turns",
+        "<turns>",
+        "Cuts games played by the bot short after this many turns (default 20000)",
+    ),
+    (
+        "--dump-map",
+        "<depth>",
+        "Prints a level generated at this depth as text",
+    ),
+    (
+        "--simulate",
+        "<games>",
+        "Plays this many games with the bot and writes a CSV row for each",
+    ),
+    (
+        "--first-seed",
+        "<seed>",
+        "The seed of the first simulated game (default 1)",
+    ),
+    (
+        "--output",
+        "<file>",
+        "Where the simulation is written (default simulation.csv)",
+    ),
+    ("--help", "", "Lists these options"),
+];
+
+/// What the game was started to do.
+pub enum Mode {
+    /// Opens the window, as usual.
+    Play,
+    Headless,
+    DumpMap {
+        depth: i32,
+    },
+    Simulate(SimulationOptions),
+    Help,
+}
+
+pub struct Options {
+    pub mode: Mode,
+    pub seed: Option<u64>,
+    pub save_dir: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub wizard: bool,
+    /// The width and height to generate levels at, if not the full size.
+    pub level_size: Option<(i32, i32)>,
+    pub max_turns: i32,
+}
+
+impl Options {
+    pub fn from_args(args: &[String]) -> Result<Options, String> {
+        let mut i = 1;
+        while let Some(arg) = args.get(i) {
+            match FLAGS.iter().find(|(flag, _value, _help)| flag == arg) {
+                Some((_flag, "", _help)) => i += 1,
+                Some(_) => i += 2,
+                None => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+
+        let flag = |flag: &str| args.iter().any(|arg| arg == flag);
+        let value = |flag: &str| -> Result<Option<&String>, String> {
+            match args.iter().position(|arg| arg == flag) {
+                None => Ok(None),
+                Some(i) => args
+                    .get(i + 1)
+                    .map(Some)
+                    .ok_or(format!("{} needs a value", flag)),
+            }
+        };
+        let number = |flag: &str, min: i64, max: i64| -> Result<Option<i64>, String> {
+            let Some(value) = value(flag)? else {
+                return Ok(None);
+            };
+            match value.parse() {
+                Ok(number) if (min..=max).contains(&number) => Ok(Some(number)),
+                _ => Err(format!("{} expects a number from {} to {}", flag, min, max)),
+            }
+        };
+
+        let seed = |flag: &str| -> Result<Option<u64>, String> {
+            value(flag)?
+                .map(|seed| {
+                    seed.parse()
+                        .map_err(|_| format!("{} expects a number", flag))
+                })
+                .transpose()
+        };
+
+        let width = number("--width", MIN_MAP_WIDTH.into(), MAP_WIDTH as i64)?;
+        let height = number("--height", MIN_MAP_HEIGHT.into(), MAP_HEIGHT as i64)?;
+        let max_turns =
+            number("--max-turns", 1, i32::MAX.into())?.map_or(20_000, |turns| turns as i32);
+        let mode = if flag("--help") {
+            Mode::Help
+        } else if let Some(games) = number("--simulate", 1, i64::MAX)? {
+            Mode::Simulate(SimulationOptions {
+                games: games as u64,
+                first_seed: seed("--first-seed")?.unwrap_or(1),
+                max_turns,
+                output: value("--output")?.map_or(PathBuf::from("simulation.csv"), PathBuf::from),
+            })
+        } else if let Some(depth) = number("--dump-map", 1, i32::MAX.into())? {
+            Mode::DumpMap {
+                depth: depth as i32,
+            }
+        } else if flag("--headless") {
+            Mode::Headless
+        } else {
+            Mode::Play
+        };
+        Ok(Options {
+            mode,
+            seed: seed("--seed")?,
+            save_dir: value("--save-dir")?.map(PathBuf::from),
+            replay: value("--replay")?.map(PathBuf::from),
+            wizard: flag("--wizard"),
+            level_size: (width.is_some() || height.is_some()).then(|| {
+                (
+                    width.map_or(MAP_WIDTH as i32, |width| width as i32),
+                    height.map_or(MAP_HEIGHT as i32, |height| height as i32),
+                )
+            }),
+            max_turns,
+        })
+    }
+}
+
+/// The list of options, for `--help` and mistakes.
+pub fn usage() -> String {
+    let mut lines = vec!["Usage: roguespace [options]".to_string(), String::new()];
+    for (flag, value, help) in FLAGS {
+        lines.push(format!("  {:<22}{}", format!("{} {}", flag, value), help));
+    }
+    lines.join("\n")
+}
+
+/// A level generated at `depth` from `seed`, revealed in full and drawn as in a morgue file.
+/// A run rolls all its levels with the one generator, so this isn't the level a run from `seed`
+/// reaches at `depth`, only one of the same kind.
+pub fn dump_map(seed: u64, depth: i32) -> String {
+    let mut gs = State {
+        ecs: new_world(seed),
+    };
+    if depth > 1 {
+        gs.ecs.fetch_mut::<Map>().depth = depth - 1;
+        gs.goto_next_level();
+        gs.ecs.maintain();
+    }
+    {
+        let mut map = gs.ecs.fetch_mut::<Map>();
+        map.revealed_tiles.fill(true);
+        map.visible_tiles.fill(true);
+    }
+    morgue::map_dump(&gs.ecs)
+}
+
+/// Plays the replay in `options` through, or else has the bot play a game from `seed`, without
+/// a window, and sums up how it went.
+pub fn play_headless(options: &Options, seed: u64) -> io::Result<GameSummary> {
+    match &options.replay {
+        Some(path) => {
+            let gs = replay::play_to_end(replay::read_replay(path)?);
+            Ok(simulation::summarize(&gs.ecs))
+        }
+        None => Ok(simulation::play_game(seed, options.max_turns)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunState, new_world_of_size};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn options_are_read_from_the_command_line() {
+        let options = Options::from_args(&args("roguespace")).unwrap();
+        assert!(matches!(options.mode, Mode::Play));
+        assert!(options.seed.is_none() && options.level_size.is_none() && !options.wizard);
+
+        let options = Options::from_args(&args(
+            "roguespace --seed 12 --width 60 --wizard --save-dir saves --dump-map 3",
+        ))
+        .unwrap();
+        assert!(matches!(options.mode, Mode::DumpMap { depth: 3 }));
+        assert_eq!(options.seed, Some(12));
+        assert_eq!(options.level_size, Some((60, MAP_HEIGHT as i32)));
+        assert_eq!(options.save_dir, Some(PathBuf::from("saves")));
+        assert!(options.wizard);
+
+        let options = Options::from_args(&args("roguespace --headless --replay run.json")).unwrap();
+        assert!(matches!(options.mode, Mode::Headless));
+        assert_eq!(options.replay, Some(PathBuf::from("run.json")));
+
+        let options = Options::from_args(&args(
+            "roguespace --simulate 10 --first-seed 5 --max-turns 300",
+        ))
+        .unwrap();
+        let Mode::Simulate(simulation) = options.mode else {
+            panic!("Not simulating");
+        };
+        assert_eq!((simulation.games, simulation.first_seed), (10, 5));
+        assert_eq!(simulation.max_turns, 300);
+        assert_eq!(simulation.output, PathBuf::from("simulation.csv"));
+
+        assert!(Options::from_args(&args("roguespace --simulate lots")).is_err());
+        assert!(Options::from_args(&args("roguespace --max-turns 5000000000")).is_err());
+        assert!(Options::from_args(&args("roguespace --width 200")).is_err());
+        assert!(Options::from_args(&args("roguespace --dump-map 0")).is_err());
+        assert!(Options::from_args(&args("roguespace --seed")).is_err());
+        assert!(Options::from_args(&args("roguespace --fast")).is_err());
+    }
+
+    #[test]
+    fn dumped_maps_depend_only_on_seed_and_depth() {
+        let map = dump_map(6, 3);
+        assert_eq!(map, dump_map(6, 3));
+        assert_ne!(map, dump_map(6, 2));
+        assert_ne!(map, dump_map(7, 3));
+        assert!(map.contains('@') && map.contains('>'));
+        assert!(map.lines().all(|line| line.len() <= MAP_WIDTH));
+    }
+
+    #[test]
+    fn smaller_levels_stay_small() {
+        let mut gs = State {
+            ecs: new_world_of_size(8, (MIN_MAP_WIDTH, MIN_MAP_HEIGHT)),
+        };
+        gs.resolve(RunState::PreRun);
+        gs.goto_next_level();
+        let map = gs.ecs.fetch::<Map>();
+        assert_eq!((map.width, map.height), (MIN_MAP_WIDTH, MIN_MAP_HEIGHT));
+        assert_eq!(map.tiles.len(), (MIN_MAP_WIDTH * MIN_MAP_HEIGHT) as usize);
+    }
+}
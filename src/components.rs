@@ -1,4 +1,3 @@
-use rltk::RGB;
 use serde::{Deserialize, Serialize};
 #[allow(deprecated)]
 use specs::error::NoError;
@@ -14,9 +13,8 @@ pub struct Position {
 
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct Renderable {
-    pub glyph: rltk::FontCharType,
-    pub fg: RGB,
-    pub bg: RGB,
+    /// What the entity looks like, by name; see `theme`.
+    pub look: String,
     pub render_order: i32,
 }
 
@@ -72,7 +70,10 @@ impl SufferDamage {
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
-pub struct Item {}
+pub struct Item {
+    pub weight: f32,
+    pub base_value: i32,
+}
 
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct Potion {
@@ -134,6 +135,12 @@ pub struct SerializeMe;
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
     pub map: super::map::Map,
+    pub identification: super::identification_system::IdentificationMap,
+    pub log: super::gamelog::GameLog,
+    pub rng: rltk::RandomNumberGenerator,
+    pub run_info: super::run_info::RunInfo,
+    pub recording: super::replay::Recording,
+    pub resume_state: super::saveload_system::ResumeState,
 }
 
 #[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
@@ -168,11 +175,6 @@ pub struct WantsToRemoveItem {
     pub item: Entity,
 }
 
-#[derive(Component, Serialize, Deserialize, Clone)]
-pub struct ParticleLifetime {
-    pub lifetime_ms: f32,
-}
-
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
 pub enum HungerState {
     WellFed,
@@ -189,3 +191,127 @@ pub struct HungerClock {
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct ProvidesFood {}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct ObfuscatedName {
+    pub name: String,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct IdentifiedItem {
+    pub names: Vec<String>,
+}
+impl IdentifiedItem {
+    pub fn new_identification(
+        store: &mut WriteStorage<IdentifiedItem>,
+        identifier: Entity,
+        name: &str,
+    ) {
+        if let Some(identified) = store.get_mut(identifier) {
+            identified.names.push(name.to_string());
+        } else {
+            let id = IdentifiedItem {
+                names: vec![name.to_string()],
+            };
+            store
+                .insert(identifier, id)
+                .expect("Unable to insert identification");
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesIdentification {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct CursedItem {}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesRemoveCurse {}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum MagicItemClass {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct MagicItem {
+    pub class: MagicItemClass,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Faction {
+    pub name: String,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct LifeSteal {
+    pub percent: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct FactionSlayer {
+    pub faction: String,
+    pub damage: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct Regeneration {
+    pub amount: i32,
+    pub interval: i32,
+    pub countdown: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct ViewRangeBonus {
+    pub range: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct SlowsHunger {
+    pub percent: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct Charges {
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct ProvidesRecharge {}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Burdened {
+    /// Set after every other action, so the next one gives the monsters an extra turn.
+    pub lagging: bool,
+    /// Whether the monsters are still owed the extra turn for the current action.
+    #[serde(default)]
+    pub extra_turn: bool,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Wallet {
+    pub credits: i32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Currency {
+    pub amount: i32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Vendor {}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct LootContainer {
+    pub credits: i32,
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToOpenContainer {
+    pub container: Entity,
+}
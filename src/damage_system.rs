@@ -1,4 +1,8 @@
-use super::{CombatStats, Map, Name, Player, Position, RunState, SufferDamage, gamelog::GameLog};
+use super::{
+    CombatStats, Map, Monster, Name, Player, Position, RunState, SufferDamage, gamelog::GameLog,
+    spawner, wizard::WizardPowers,
+};
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 
 pub struct DamageSystem {}
@@ -10,12 +14,16 @@ impl<'a> System<'a> for DamageSystem {
         ReadStorage<'a, Position>,
         WriteExpect<'a, Map>,
         Entities<'a>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, WizardPowers>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
+        let (mut stats, mut damage, positions, mut map, entities, player_entity, powers) = data;
         for (entity, stats, damage) in (&entities, &mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+            if !(powers.god_mode && entity == *player_entity) {
+                stats.hp -= damage.amount.iter().sum::<i32>();
+            }
             let pos = positions.get(entity);
             if let Some(pos) = pos {
                 let idx = map.xy_idx(pos.x, pos.y);
@@ -29,11 +37,14 @@ impl<'a> System<'a> for DamageSystem {
 
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut drops: Vec<(i32, i32)> = Vec::new();
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let entities = ecs.entities();
         let names = ecs.read_storage::<Name>();
+        let monsters = ecs.read_storage::<Monster>();
+        let positions = ecs.read_storage::<Position>();
         let mut log = ecs.fetch_mut::<GameLog>();
         for (entity, stats) in (&entities, &combat_stats).join() {
             if stats.hp < 1 {
@@ -44,6 +55,11 @@ pub fn delete_the_dead(ecs: &mut World) {
                         if let Some(victim_name) = victim_name {
                             log.entries.push(format!("{} is dead.", &victim_name.name));
                         }
+                        if let (Some(_monster), Some(pos)) =
+                            (monsters.get(entity), positions.get(entity))
+                        {
+                            drops.push((pos.x, pos.y));
+                        }
                         dead.push(entity)
                     }
                     Some(_) => {
@@ -58,4 +74,20 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete.");
     }
+
+    // Slain monsters sometimes leave behind the credits they were carrying
+    for (x, y) in drops {
+        let amount = {
+            let depth = ecs.fetch::<Map>().depth;
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            if rng.roll_dice(1, 2) == 1 {
+                rng.roll_dice(1, 6) + depth * 2
+            } else {
+                0
+            }
+        };
+        if amount > 0 {
+            spawner::credit_chip(ecs, x, y, amount);
+        }
+    }
 }
@@ -0,0 +1,163 @@
+use super::{Burdened, CombatStats, Equipped, InBackpack, Item, gamelog::GameLog};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+/// Beyond this multiple of their capacity, an entity refuses to pick anything else up.
+pub const OVERLOAD_MULTIPLIER: f32 = 1.5;
+
+pub fn carry_capacity(stats: &CombatStats) -> f32 {
+    10.0 + stats.power as f32 * 5.0
+}
+
+pub fn carried_weight<B, E>(
+    owner: Entity,
+    items: &ReadStorage<Item>,
+    backpack: &Storage<InBackpack, B>,
+    equipped: &Storage<Equipped, E>,
+) -> f32
+where
+    B: Deref<Target = MaskedStorage<InBackpack>>,
+    E: Deref<Target = MaskedStorage<Equipped>>,
+{
+    let mut weight = 0.0;
+    for (item, pack) in (items, backpack).join() {
+        if pack.owner == owner {
+            weight += item.weight;
+        }
+    }
+    for (item, worn) in (items, equipped).join() {
+        if worn.owner == owner {
+            weight += item.weight;
+        }
+    }
+    weight
+}
+
+pub struct EncumbranceSystem {}
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, Burdened>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_entity,
+            mut log,
+            items,
+            backpack,
+            equipped,
+            combat_stats,
+            mut burdened,
+        ) = data;
+
+        let mut carried: HashMap<Entity, f32> = HashMap::new();
+        for (item, pack) in (&items, &backpack).join() {
+            *carried.entry(pack.owner).or_insert(0.0) += item.weight;
+        }
+        for (item, worn) in (&items, &equipped).join() {
+            *carried.entry(worn.owner).or_insert(0.0) += item.weight;
+        }
+
+        for (entity, stats) in (&entities, &combat_stats).join() {
+            let weight = carried.get(&entity).copied().unwrap_or(0.0);
+            let is_burdened = weight > carry_capacity(stats);
+            let was_burdened = burdened.get(entity).is_some();
+
+            if is_burdened && !was_burdened {
+                burdened
+                    .insert(
+                        entity,
+                        Burdened {
+                            lagging: false,
+                            extra_turn: false,
+                        },
+                    )
+                    .expect("Unable to insert burden");
+                if entity == *player_entity {
+                    log.entries
+                        .push("You are burdened by your load, and slow down.".to_string());
+                }
+            } else if !is_burdened && was_burdened {
+                burdened.remove(entity);
+                if entity == *player_entity {
+                    log.detail("You are no longer burdened.".to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Burdened players give the monsters an extra turn after every second action. Called once for
+/// each action the player takes.
+pub fn note_burdened_action(ecs: &mut World) {
+    let player_entity = ecs.fetch::<Entity>();
+    let mut burdened = ecs.write_storage::<Burdened>();
+    if let Some(burden) = burdened.get_mut(*player_entity) {
+        burden.extra_turn = burden.lagging;
+        burden.lagging = !burden.lagging;
+    }
+}
+
+/// Whether the monsters get another turn before the player acts again, which they only ever
+/// do once for an action.
+pub fn burden_grants_extra_turn(ecs: &mut World) -> bool {
+    let player_entity = ecs.fetch::<Entity>();
+    let mut burdened = ecs.write_storage::<Burdened>();
+    burdened
+        .get_mut(*player_entity)
+        .is_some_and(|burden| std::mem::take(&mut burden.extra_turn))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunState, State, new_world};
+
+    #[test]
+    fn burdened_players_give_an_extra_turn_every_other_action() {
+        let mut gs = State { ecs: new_world(4) };
+        gs.resolve(RunState::PreRun);
+        let player_entity = *gs.ecs.fetch::<Entity>();
+        gs.ecs
+            .create_entity()
+            .with(Item {
+                weight: 1000.0,
+                base_value: 0,
+            })
+            .with(InBackpack {
+                owner: player_entity,
+            })
+            .build();
+
+        let mut monster_turns = 0;
+        for _action in 0..6 {
+            let mut state = RunState::PlayerTurn;
+            while state != RunState::AwaitingInput {
+                if state == RunState::MonsterTurn {
+                    monster_turns += 1;
+                }
+                gs.end_step(state);
+                state = gs.advance(state);
+            }
+        }
+        assert!(
+            gs.ecs
+                .read_storage::<Burdened>()
+                .get(player_entity)
+                .is_some()
+        );
+        assert_eq!(monster_turns, 9);
+    }
+}
@@ -1,3 +1,16 @@
+use super::settings::{self, Verbosity};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct GameLog {
     pub entries: Vec<String>,
 }
+
+impl GameLog {
+    /// Logs `message` unless the player has asked for brief messages.
+    pub fn detail(&mut self, message: String) {
+        if settings::current().messages == Verbosity::Full {
+            self.entries.push(message);
+        }
+    }
+}
@@ -1,11 +1,22 @@
 use super::{
-    CombatStats, Equipped, HungerClock, HungerState, InBackpack, Map, Name, Player, Position,
-    RunState, State, Viewshed, gamelog::GameLog,
+    Burdened, Charges, CombatStats, Equipped, HungerClock, HungerState, InBackpack, Item,
+    MagicItem, Map, Name, ObfuscatedName, Player, Position, RunState, State, Viewshed, Wallet,
+    backend,
+    encumbrance_system::{carried_weight, carry_capacity},
+    gamelog::GameLog,
+    identification_system::{IdentificationMap, get_item_display_name},
+    keymap::{self, Action, Context},
+    loot_generator::rarity_colour,
+    morgue,
+    run_info::RunInfo,
+    saveload_system::{self, SAVE_SLOTS},
+    settings, theme, vendor, wizard,
 };
-use rltk::{BTerm, Point, RGB, Rltk, VirtualKeyCode};
+use rltk::{Point, RGB, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 
 pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
+    let theme = theme::current();
     ctx.draw_box(
         0,
         43,
@@ -18,7 +29,10 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     let combat_stats = ecs.read_storage::<CombatStats>();
     let players = ecs.read_storage::<Player>();
     let hunger = ecs.read_storage::<HungerClock>();
-    for (_player, stats, hc) in (&players, &combat_stats, &hunger).join() {
+    let burdened = ecs.read_storage::<Burdened>();
+    let wallets = ecs.read_storage::<Wallet>();
+    let entities = ecs.entities();
+    for (entity, _player, stats, hc) in (&entities, &players, &combat_stats, &hunger).join() {
         let health = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
         ctx.print_color(
             12,
@@ -37,38 +51,55 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
             RGB::named(rltk::RED),
             RGB::named(rltk::BLACK),
         );
-        match hc.state {
-            HungerState::WellFed => ctx.print_color(
-                71,
+        let hunger = match hc.state {
+            HungerState::WellFed => Some(("Well Fed", "ui.hunger.well_fed", "+")),
+            HungerState::Normal => None,
+            HungerState::Hungry => Some(("Hungry", "ui.hunger.hungry", "!")),
+            HungerState::Starving => Some(("Starving", "ui.hunger.starving", "!!")),
+        };
+        // Statuses are laid out leftwards from the right edge, so they never overlap
+        let mut status_right = 79;
+        if let Some((label, look, cue)) = hunger {
+            let text = if theme.cues {
+                format!("{} {}", label, cue)
+            } else {
+                label.to_string()
+            };
+            status_right -= text.len() as i32;
+            ctx.print_color(
+                status_right,
                 42,
-                RGB::named(rltk::GREEN),
+                theme.look(look).fg,
                 RGB::named(rltk::BLACK),
-                "Well Fed",
-            ),
-            HungerState::Normal => {}
-            HungerState::Hungry => ctx.print_color(
-                71,
+                text,
+            );
+            status_right -= 1;
+        }
+        if let Some(wallet) = wallets.get(entity) {
+            ctx.print_color(
+                2,
                 42,
-                RGB::named(rltk::ORANGE),
+                theme.look("ui.credits").fg,
                 RGB::named(rltk::BLACK),
-                "Hungry",
-            ),
-            HungerState::Starving => ctx.print_color(
-                71,
+                format!("Credits: {}", wallet.credits),
+            );
+        }
+        if burdened.get(entity).is_some() {
+            let text = "Burdened";
+            ctx.print_color(
+                status_right - text.len() as i32,
                 42,
-                RGB::named(rltk::RED),
+                theme.look("ui.burdened").fg,
                 RGB::named(rltk::BLACK),
-                "Starving",
-            ),
+                text,
+            );
         }
     }
     let log = ecs.fetch::<GameLog>();
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
+    for (y, s) in (44..).zip(log.entries.iter().rev()) {
         if y < 49 {
             ctx.print(2, y, s);
         }
-        y += 1;
     }
     let mouse_pos = ctx.mouse_pos();
     ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
@@ -89,17 +120,19 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
 fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
+    let obfuscated_names = ecs.read_storage::<ObfuscatedName>();
     let positions = ecs.read_storage::<Position>();
+    let entities = ecs.entities();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
         return;
     }
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    for (entity, _name, position) in (&entities, &names, &positions).join() {
         let idx = map.xy_idx(position.x, position.y);
         if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
-            tooltip.push(name.name.to_string());
+            tooltip.push(get_item_display_name(&names, &obfuscated_names, entity));
         }
     }
 
@@ -115,8 +148,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
         if mouse_pos.0 > 40 {
             let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             let left_x = mouse_pos.0 - width;
-            let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
+            for (y, s) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x,
                     y,
@@ -134,7 +166,6 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         " ".to_string(),
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x,
@@ -146,8 +177,7 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
         } else {
             let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
             let left_x = mouse_pos.0 + 3;
-            let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
+            for (y, s) in (mouse_pos.1..).zip(tooltip.iter()) {
                 ctx.print_color(
                     left_x + 1,
                     y,
@@ -165,7 +195,6 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
                         " ".to_string(),
                     );
                 }
-                y += 1;
             }
             ctx.print_color(
                 arrow_pos.x,
@@ -190,21 +219,45 @@ fn handle_item_selection(
     count: usize,
     equippable: &[Entity],
 ) -> (ItemMenuResult, Option<Entity>) {
+    if keymap::pressed(ctx, Context::Menu) == Some(Action::MenuBack) {
+        return (ItemMenuResult::Cancel, None);
+    }
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
-        Some(key) => match key {
-            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
-            _ => {
-                let selection = rltk::letter_to_option(key);
-                if selection > -1 && selection < count as i32 {
-                    return (
-                        ItemMenuResult::Selected,
-                        Some(equippable[selection as usize]),
-                    );
-                }
-                (ItemMenuResult::NoResponse, None)
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return (
+                    ItemMenuResult::Selected,
+                    Some(equippable[selection as usize]),
+                );
             }
-        },
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+fn menu_box_width(display_names: &[String]) -> i32 {
+    let longest = display_names.iter().map(|n| n.len()).max().unwrap_or(0) as i32;
+    i32::max(31, longest + 8)
+}
+
+/// The rarity colour of a magic item, once the player has identified it, and white otherwise.
+/// Equipment is identified piece by piece, so it counts once it's no longer shown under an alias.
+fn item_colour(
+    magic_items: &ReadStorage<MagicItem>,
+    names: &ReadStorage<Name>,
+    obfuscated_names: &ReadStorage<ObfuscatedName>,
+    identification: &IdentificationMap,
+    item: Entity,
+) -> RGB {
+    let identified = obfuscated_names.get(item).is_none()
+        || names
+            .get(item)
+            .is_some_and(|name| identification.identified_items.contains(&name.name));
+    match magic_items.get(item) {
+        Some(magic) if identified => rarity_colour(magic.class),
+        _ => RGB::named(rltk::WHITE),
     }
 }
 
@@ -212,29 +265,61 @@ fn item_menu(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Op
     use std::collections::HashMap;
 
     let player_entity = gs.ecs.fetch::<Entity>();
+    let weight_label = {
+        let items = gs.ecs.read_storage::<Item>();
+        let backpack = gs.ecs.read_storage::<InBackpack>();
+        let equipped = gs.ecs.read_storage::<Equipped>();
+        let combat_stats = gs.ecs.read_storage::<CombatStats>();
+        let weight = carried_weight(*player_entity, &items, &backpack, &equipped);
+        let capacity = combat_stats.get(*player_entity).map_or(0.0, carry_capacity);
+        format!(" {:.1} / {:.1} lbs ", weight, capacity)
+    };
     let names = gs.ecs.read_storage::<Name>();
+    let obfuscated_names = gs.ecs.read_storage::<ObfuscatedName>();
+    let magic_items = gs.ecs.read_storage::<MagicItem>();
+    let identification = gs.ecs.fetch::<IdentificationMap>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
     let entities = gs.ecs.entities();
 
+    let charges = gs.ecs.read_storage::<Charges>();
+
     let mut item_groups: HashMap<String, Vec<Entity>> = HashMap::new();
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    for (entity, _pack, _name) in (&entities, &backpack, &names)
         .join()
         .filter(|item| item.1.owner == *player_entity)
     {
-        item_groups
-            .entry(name.name.clone())
-            .or_default()
-            .push(entity);
+        let mut item_name = get_item_display_name(&names, &obfuscated_names, entity);
+        if let Some(charge) = charges.get(entity) {
+            item_name = match charge.current {
+                1 => format!("{} (1 charge)", item_name),
+                n => format!("{} ({} charges)", item_name, n),
+            };
+        }
+        item_groups.entry(item_name).or_default().push(entity);
     }
 
     let mut unique_items: Vec<(String, Vec<Entity>)> = item_groups.into_iter().collect();
     unique_items.sort_by(|a, b| a.0.cmp(&b.0));
 
+    let display_names: Vec<String> = unique_items
+        .iter()
+        .map(|(item_name, item_entities)| {
+            if item_entities.len() > 1 {
+                format!("{}x {}", item_entities.len(), item_name)
+            } else {
+                item_name.clone()
+            }
+        })
+        .collect();
+
     let count = unique_items.len();
     let mut y = (25 - (count / 2)) as i32;
 
-    BTerm::set_active_console(ctx, 0);
-    let box_width = 31;
+    backend::set_layer(ctx, backend::MAP_LAYER);
+    let box_width = i32::max(
+        menu_box_width(&display_names),
+        (title.len() + weight_label.len()) as i32 + 6,
+    );
     let box_height = (count + 3) as i32;
     for dy in 0..=box_height {
         for dx in 0..=box_width {
@@ -248,11 +333,11 @@ fn item_menu(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Op
         }
     }
 
-    BTerm::set_active_console(ctx, 2);
+    backend::set_layer(ctx, backend::TEXT_LAYER);
     ctx.draw_box(
         15,
         y - 2,
-        31,
+        box_width,
         (count + 3) as i32,
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
@@ -264,16 +349,25 @@ fn item_menu(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Op
         RGB::named(rltk::BLACK),
         title,
     );
+    ctx.print_color(
+        15 + box_width - weight_label.len() as i32 - 1,
+        y - 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        &weight_label,
+    );
     ctx.print_color(
         18,
         y + count as i32 + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        format!("{} to cancel", keymap::describe(Action::MenuBack)),
     );
 
     let mut equippable: Vec<Entity> = Vec::new();
-    for (j, (item_name, item_entities)) in unique_items.iter().enumerate() {
+    for (j, ((_item_name, item_entities), display_name)) in
+        unique_items.iter().zip(display_names.iter()).enumerate()
+    {
         ctx.set(
             17,
             y,
@@ -296,13 +390,19 @@ fn item_menu(gs: &mut State, ctx: &mut Rltk, title: &str) -> (ItemMenuResult, Op
             rltk::to_cp437(')'),
         );
 
-        let display_name = if item_entities.len() > 1 {
-            format!("{}x {}", item_entities.len(), item_name)
-        } else {
-            item_name.clone()
-        };
-
-        ctx.print(21, y, &display_name);
+        ctx.print_color(
+            21,
+            y,
+            item_colour(
+                &magic_items,
+                &names,
+                &obfuscated_names,
+                &identification,
+                item_entities[0],
+            ),
+            RGB::named(rltk::BLACK),
+            display_name,
+        );
         equippable.push(item_entities[0]);
         y += 1;
     }
@@ -318,70 +418,132 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     item_menu(gs, ctx, "Drop Which Item?")
 }
 
-pub fn ranged_target(
-    gs: &mut State,
-    ctx: &mut Rltk,
-    range: i32,
-) -> (ItemMenuResult, Option<Point>) {
+/// Where the player is aiming on the targeting screen. The cursor moves with the menu keys, and
+/// jumps to the mouse whenever it moves.
+#[derive(PartialEq, Copy, Clone)]
+pub struct Aim {
+    pub cursor: Point,
+    /// Where the mouse was last frame.
+    pub mouse: (i32, i32),
+}
+
+impl Aim {
+    /// Aims at the player to begin with.
+    pub fn new(ecs: &World, ctx: &Rltk) -> Aim {
+        Aim {
+            cursor: *ecs.fetch::<Point>(),
+            mouse: ctx.mouse_pos(),
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum TargetResult {
+    NoResponse { aim: Aim },
+    Cancel,
+    Selected { target: Point },
+}
+
+pub fn ranged_target(gs: &mut State, ctx: &mut Rltk, range: i32, mut aim: Aim) -> TargetResult {
     let player_entity = gs.ecs.fetch::<Entity>();
     let player_pos = gs.ecs.fetch::<Point>();
     let viewsheds = gs.ecs.read_storage::<Viewshed>();
 
-    BTerm::set_active_console(ctx, 2);
+    let theme = theme::current();
+    let prompt = format!(
+        "Select Target ({} to fire, {} to cancel):",
+        keymap::describe(Action::MenuSelect),
+        keymap::describe(Action::MenuBack)
+    );
+    backend::set_layer(ctx, backend::TEXT_LAYER);
     ctx.print_color(
         5,
         0,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Select Target:",
+        &prompt,
     );
 
-    BTerm::set_active_console(ctx, 0);
+    backend::set_layer(ctx, backend::MAP_LAYER);
     let mut available_cells = Vec::new();
     let visible = viewsheds.get(*player_entity);
     if let Some(visible) = visible {
         for idx in visible.visible_tiles.iter() {
             let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *idx);
             if distance <= range as f32 {
-                ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
+                ctx.set_bg(idx.x, idx.y, theme.look("ui.target.range").fg);
                 available_cells.push(idx);
             }
         }
     } else {
-        return (ItemMenuResult::Cancel, None);
+        return TargetResult::Cancel;
     }
 
     let mouse_pos = ctx.mouse_pos();
-    let mut valid_target = false;
-    for idx in available_cells.iter() {
-        if idx.x == mouse_pos.0 && idx.y == mouse_pos.1 {
-            valid_target = true;
-        }
+    if mouse_pos != aim.mouse || ctx.left_click {
+        aim.cursor = Point::new(mouse_pos.0, mouse_pos.1);
+        aim.mouse = mouse_pos;
+    }
+    let pressed = keymap::pressed(ctx, Context::Menu);
+    match pressed {
+        Some(Action::MenuUp) => aim.cursor.y -= 1,
+        Some(Action::MenuDown) => aim.cursor.y += 1,
+        Some(Action::MenuLeft) => aim.cursor.x -= 1,
+        Some(Action::MenuRight) => aim.cursor.x += 1,
+        Some(Action::MenuBack) => return TargetResult::Cancel,
+        _ => {}
     }
+
+    let valid_target = available_cells.iter().any(|idx| **idx == aim.cursor);
+    if theme.cues {
+        backend::set_layer(ctx, backend::TEXT_LAYER);
+        ctx.print_color(
+            6 + prompt.len() as i32,
+            0,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            if valid_target {
+                "in range"
+            } else {
+                "out of range"
+            },
+        );
+        backend::set_layer(ctx, backend::MAP_LAYER);
+    }
+    let fired = ctx.left_click || pressed == Some(Action::MenuSelect);
     if valid_target {
-        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
-        if ctx.left_click {
-            return (
-                ItemMenuResult::Selected,
-                Some(Point::new(mouse_pos.0, mouse_pos.1)),
-            );
+        ctx.set_bg(aim.cursor.x, aim.cursor.y, theme.look("ui.target.valid").fg);
+        if fired {
+            return TargetResult::Selected { target: aim.cursor };
         }
     } else {
-        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
+        ctx.set_bg(
+            aim.cursor.x,
+            aim.cursor.y,
+            theme.look("ui.target.invalid").fg,
+        );
         if ctx.left_click {
-            return (ItemMenuResult::Cancel, None);
+            return TargetResult::Cancel;
         }
     }
-    (ItemMenuResult::NoResponse, None)
+    TargetResult::NoResponse { aim }
 }
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum MainMenuSelection {
     NewGame,
     LoadGame,
+    HighScores,
+    Options,
     Quit,
 }
 
+/// A message shown beneath the main menu, such as why a save could not be loaded.
+#[derive(Default)]
+pub struct MainMenuNotice {
+    pub message: Option<String>,
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum MainMenuResult {
     NoSelection { selected: MainMenuSelection },
@@ -391,6 +553,10 @@ pub enum MainMenuResult {
 pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     let save_exists = super::saveload_system::does_save_exist();
     let run_state = gs.ecs.fetch::<RunState>();
+    let mut notice = gs.ecs.fetch_mut::<MainMenuNotice>();
+    if let Some(message) = &notice.message {
+        ctx.print_color_centered(30, RGB::named(rltk::RED), RGB::named(rltk::BLACK), message);
+    }
 
     ctx.print_color_centered(
         15,
@@ -437,59 +603,93 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
             }
         }
 
-        if selection == MainMenuSelection::Quit {
+        if selection == MainMenuSelection::HighScores {
             ctx.print_color_centered(
                 26,
                 RGB::named(rltk::MAGENTA),
                 RGB::named(rltk::BLACK),
+                "High Scores",
+            );
+        } else {
+            ctx.print_color_centered(
+                26,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                "High Scores",
+            );
+        }
+
+        if selection == MainMenuSelection::Options {
+            ctx.print_color_centered(
+                27,
+                RGB::named(rltk::MAGENTA),
+                RGB::named(rltk::BLACK),
+                "Options",
+            );
+        } else {
+            ctx.print_color_centered(
+                27,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                "Options",
+            );
+        }
+
+        if selection == MainMenuSelection::Quit {
+            ctx.print_color_centered(
+                28,
+                RGB::named(rltk::MAGENTA),
+                RGB::named(rltk::BLACK),
                 "Quit",
             );
         } else {
-            ctx.print_color_centered(26, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+            ctx.print_color_centered(28, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
         }
 
-        return match ctx.key {
-            None => MainMenuResult::NoSelection {
-                selected: selection,
+        return match keymap::pressed(ctx, Context::Menu) {
+            Some(Action::MenuBack) => MainMenuResult::NoSelection {
+                selected: MainMenuSelection::Quit,
             },
-            Some(key) => match key {
-                VirtualKeyCode::Escape => MainMenuResult::NoSelection {
-                    selected: MainMenuSelection::Quit,
-                },
-                VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                    let mut new_selection;
-                    match selection {
-                        MainMenuSelection::NewGame => new_selection = MainMenuSelection::Quit,
-                        MainMenuSelection::LoadGame => new_selection = MainMenuSelection::NewGame,
-                        MainMenuSelection::Quit => new_selection = MainMenuSelection::LoadGame,
-                    }
-                    if new_selection == MainMenuSelection::LoadGame && !save_exists {
-                        new_selection = MainMenuSelection::NewGame;
-                    }
-                    MainMenuResult::NoSelection {
-                        selected: new_selection,
-                    }
+            Some(Action::MenuUp) => {
+                let mut new_selection;
+                match selection {
+                    MainMenuSelection::NewGame => new_selection = MainMenuSelection::Quit,
+                    MainMenuSelection::LoadGame => new_selection = MainMenuSelection::NewGame,
+                    MainMenuSelection::HighScores => new_selection = MainMenuSelection::LoadGame,
+                    MainMenuSelection::Options => new_selection = MainMenuSelection::HighScores,
+                    MainMenuSelection::Quit => new_selection = MainMenuSelection::Options,
                 }
-                VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                    let mut new_selection;
-                    match selection {
-                        MainMenuSelection::NewGame => new_selection = MainMenuSelection::LoadGame,
-                        MainMenuSelection::LoadGame => new_selection = MainMenuSelection::Quit,
-                        MainMenuSelection::Quit => new_selection = MainMenuSelection::NewGame,
-                    }
-                    if new_selection == MainMenuSelection::LoadGame && !save_exists {
-                        new_selection = MainMenuSelection::Quit;
-                    }
-                    MainMenuResult::NoSelection {
-                        selected: new_selection,
-                    }
+                if new_selection == MainMenuSelection::LoadGame && !save_exists {
+                    new_selection = MainMenuSelection::NewGame;
                 }
-                VirtualKeyCode::Return => MainMenuResult::Selected {
-                    selected: selection,
-                },
-                _ => MainMenuResult::NoSelection {
+                MainMenuResult::NoSelection {
+                    selected: new_selection,
+                }
+            }
+            Some(Action::MenuDown) => {
+                let mut new_selection;
+                match selection {
+                    MainMenuSelection::NewGame => new_selection = MainMenuSelection::LoadGame,
+                    MainMenuSelection::LoadGame => new_selection = MainMenuSelection::HighScores,
+                    MainMenuSelection::HighScores => new_selection = MainMenuSelection::Options,
+                    MainMenuSelection::Options => new_selection = MainMenuSelection::Quit,
+                    MainMenuSelection::Quit => new_selection = MainMenuSelection::NewGame,
+                }
+                if new_selection == MainMenuSelection::LoadGame && !save_exists {
+                    new_selection = MainMenuSelection::HighScores;
+                }
+                MainMenuResult::NoSelection {
+                    selected: new_selection,
+                }
+            }
+            Some(Action::MenuSelect) => {
+                notice.message = None;
+                MainMenuResult::Selected {
                     selected: selection,
-                },
+                }
+            }
+            _ => MainMenuResult::NoSelection {
+                selected: selection,
             },
         };
     }
@@ -499,20 +699,230 @@ pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
     }
 }
 
+/// The longest name a character can be given.
+const MAX_CHARACTER_NAME: usize = 20;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum NameEntryResult {
+    NoResponse,
+    Cancel,
+    Done,
+}
+
+/// Asks the player to name their character before a new game, typing straight into the run's
+/// `RunInfo`.
+pub fn name_character(gs: &mut State, ctx: &mut Rltk) -> NameEntryResult {
+    let mut run_info = gs.ecs.fetch_mut::<RunInfo>();
+    match keymap::pressed(ctx, Context::Menu) {
+        Some(Action::MenuBack) => return NameEntryResult::Cancel,
+        Some(Action::MenuSelect) if !run_info.character.trim().is_empty() => {
+            run_info.character = run_info.character.trim().to_string();
+            return NameEntryResult::Done;
+        }
+        _ => {}
+    }
+    if ctx.key == Some(VirtualKeyCode::Back) {
+        run_info.character.pop();
+    } else if let Some(c) = keymap::typed_char(ctx)
+        && run_info.character.len() < MAX_CHARACTER_NAME
+    {
+        run_info.character.push(c);
+    }
+
+    ctx.print_color_centered(
+        15,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "RogueSpace",
+    );
+    ctx.print_color_centered(
+        22,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        "What is your name?",
+    );
+    ctx.print_color_centered(
+        24,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        format!("{}_", run_info.character),
+    );
+    ctx.print_color_centered(
+        27,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{} to begin, {} to go back",
+            keymap::describe(Action::MenuSelect),
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+    NameEntryResult::NoResponse
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotMenuMode {
+    Save,
+    Load,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotMenuResult {
+    NoSelection { selected: usize },
+    Selected { selected: usize },
+    Delete { selected: usize },
+    Cancel,
+}
+
+pub fn save_slot_menu(ctx: &mut Rltk, mode: SlotMenuMode, selection: usize) -> SlotMenuResult {
+    let mut slots = saveload_system::save_slots();
+    let title = match mode {
+        SlotMenuMode::Save => "Save Game",
+        SlotMenuMode::Load => "Load Game",
+    };
+    // The autosave can be loaded, but not saved over by hand
+    if mode == SlotMenuMode::Save {
+        slots.truncate(SAVE_SLOTS);
+    }
+    let count = slots.len();
+
+    backend::set_layer(ctx, backend::MAP_LAYER);
+    for y in 10..=(14 + count as i32 * 3) {
+        for x in 10..70 {
+            ctx.set(x, y, RGB::named(rltk::BLACK), RGB::named(rltk::BLACK), 0);
+        }
+    }
+
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.draw_box(
+        10,
+        10,
+        59,
+        4 + count as i32 * 3,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color_centered(10, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), title);
+
+    for (i, slot) in slots.iter().enumerate() {
+        let y = 12 + i as i32 * 3;
+        let label = saveload_system::slot_label(i);
+        let fg = if i == selection {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        match slot {
+            Err(e) => {
+                ctx.print_color(
+                    13,
+                    y,
+                    fg,
+                    RGB::named(rltk::BLACK),
+                    format!("{} Damaged save", label),
+                );
+                ctx.print_color(
+                    16,
+                    y + 1,
+                    RGB::named(rltk::RED),
+                    RGB::named(rltk::BLACK),
+                    e.to_string(),
+                );
+            }
+            Ok(None) => ctx.print_color(
+                13,
+                y,
+                fg,
+                RGB::named(rltk::BLACK),
+                format!("{} Empty", label),
+            ),
+            Ok(Some(metadata)) => {
+                ctx.print_color(
+                    13,
+                    y,
+                    fg,
+                    RGB::named(rltk::BLACK),
+                    format!(
+                        "{} {}, depth {}, turn {}",
+                        label, metadata.character, metadata.depth, metadata.turns
+                    ),
+                );
+                ctx.print_color(
+                    16,
+                    y + 1,
+                    RGB::named(rltk::GRAY),
+                    RGB::named(rltk::BLACK),
+                    format!("Saved {}, seed {}", metadata.saved_at(), metadata.seed),
+                );
+            }
+        }
+    }
+
+    ctx.print_color(
+        13,
+        13 + count as i32 * 3,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{} to select, {} to erase, {} to cancel",
+            keymap::describe(Action::MenuSelect),
+            keymap::describe(Action::MenuDelete),
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+
+    match keymap::pressed(ctx, Context::Menu) {
+        Some(Action::MenuBack) => SlotMenuResult::Cancel,
+        Some(Action::MenuUp) => SlotMenuResult::NoSelection {
+            selected: (selection + count - 1) % count,
+        },
+        Some(Action::MenuDown) => SlotMenuResult::NoSelection {
+            selected: (selection + 1) % count,
+        },
+        Some(Action::MenuDelete) if !matches!(slots[selection], Ok(None)) => {
+            SlotMenuResult::Delete {
+                selected: selection,
+            }
+        }
+        // Only occupied slots can be loaded, but any slot can be saved over
+        Some(Action::MenuSelect)
+            if mode == SlotMenuMode::Save || !matches!(slots[selection], Ok(None)) =>
+        {
+            SlotMenuResult::Selected {
+                selected: selection,
+            }
+        }
+        _ => SlotMenuResult::NoSelection {
+            selected: selection,
+        },
+    }
+}
+
 pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
+    let obfuscated_names = gs.ecs.read_storage::<ObfuscatedName>();
+    let magic_items = gs.ecs.read_storage::<MagicItem>();
+    let identification = gs.ecs.fetch::<IdentificationMap>();
     let backpack = gs.ecs.read_storage::<Equipped>();
     let entities = gs.ecs.entities();
 
-    let inventory = (&backpack, &names)
+    let inventory: Vec<(Entity, String)> = (&entities, &backpack, &names)
         .join()
-        .filter(|item| item.0.owner == *player_entity);
-    let count = inventory.count();
+        .filter(|item| item.1.owner == *player_entity)
+        .map(|item| {
+            (
+                item.0,
+                get_item_display_name(&names, &obfuscated_names, item.0),
+            )
+        })
+        .collect();
+    let count = inventory.len();
+    let display_names: Vec<String> = inventory.iter().map(|item| item.1.clone()).collect();
 
     let mut y = (25 - count / 2) as i32;
-    BTerm::set_active_console(ctx, 0);
-    let box_width = 31;
+    backend::set_layer(ctx, backend::MAP_LAYER);
+    let box_width = menu_box_width(&display_names);
     let box_height = (count + 3) as i32;
     for dy in 0..=box_height {
         for dx in 0..=box_width {
@@ -526,12 +936,12 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
         }
     }
 
-    BTerm::set_active_console(ctx, 2);
+    backend::set_layer(ctx, backend::TEXT_LAYER);
 
     ctx.draw_box(
         15,
         y - 2,
-        31,
+        box_width,
         (count + 3) as i32,
         RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
@@ -548,15 +958,11 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
         y + count as i32 + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "ESCAPE to cancel",
+        format!("{} to cancel", keymap::describe(Action::MenuBack)),
     );
 
     let mut equippable: Vec<Entity> = Vec::new();
-    for (j, (entity, _pack, name)) in (&entities, &backpack, &names)
-        .join()
-        .filter(|item| item.1.owner == *player_entity)
-        .enumerate()
-    {
+    for (j, (entity, display_name)) in inventory.iter().enumerate() {
         ctx.set(
             17,
             y,
@@ -579,8 +985,20 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, name.name.to_string());
-        equippable.push(entity);
+        ctx.print_color(
+            21,
+            y,
+            item_colour(
+                &magic_items,
+                &names,
+                &obfuscated_names,
+                &identification,
+                *entity,
+            ),
+            RGB::named(rltk::BLACK),
+            display_name,
+        );
+        equippable.push(*entity);
         y += 1;
     }
 
@@ -593,35 +1011,659 @@ pub enum GameOverResult {
     QuitToMenu,
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
     ctx.print_color_centered(
         15,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
         "Your journey has ended!",
     );
+    {
+        let run_info = ecs.fetch::<RunInfo>();
+        ctx.print_color_centered(
+            17,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!(
+                "You were killed by {} on depth {}, after {} turns.",
+                morgue::cause_of_death(&run_info),
+                ecs.fetch::<Map>().depth,
+                run_info.turns
+            ),
+        );
+    }
+    if let Some(record) = ecs.try_fetch::<morgue::DeathRecord>() {
+        if let Some(rank) = record.rank {
+            ctx.print_color_centered(
+                18,
+                RGB::named(rltk::GREEN),
+                RGB::named(rltk::BLACK),
+                format!("Your run is number {} on the high-score table!", rank + 1),
+            );
+        }
+        if let Some(path) = &record.morgue_file {
+            ctx.print_color_centered(
+                19,
+                RGB::named(rltk::GRAY),
+                RGB::named(rltk::BLACK),
+                format!("Morgue file: {}", path.display()),
+            );
+        }
+    }
+
     ctx.print_color_centered(
-        17,
-        RGB::named(rltk::WHITE),
+        20,
+        RGB::named(rltk::MAGENTA),
         RGB::named(rltk::BLACK),
-        "One day, we'll tell you all about how you did.",
+        return_to_menu_prompt(),
     );
+
+    if closes_screen(ctx) {
+        GameOverResult::QuitToMenu
+    } else {
+        GameOverResult::NoSelection
+    }
+}
+
+/// Lists the best runs so far. Returns true once the player goes back to the main menu.
+pub fn high_scores(ecs: &World, ctx: &mut Rltk) -> bool {
+    let scores = &ecs.fetch::<morgue::HighScoreTable>().scores;
     ctx.print_color_centered(
-        18,
-        RGB::named(rltk::WHITE),
+        8,
+        RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "That day, sadly, is not in this chapter..",
+        "High Scores",
     );
-
+    if scores.is_empty() {
+        ctx.print_color_centered(
+            11,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            "No runs have ended yet.",
+        );
+    }
+    for (i, score) in scores.iter().enumerate() {
+        let y = 11 + i as i32 * 2;
+        ctx.print_color(
+            10,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            format!(
+                "{:>2}. {}, depth {}, turn {}",
+                i + 1,
+                score.character,
+                score.depth,
+                score.turns
+            ),
+        );
+        ctx.print_color(
+            14,
+            y + 1,
+            RGB::named(rltk::GRAY),
+            RGB::named(rltk::BLACK),
+            format!("Killed by {} on {}", score.cause_of_death, score.died_on()),
+        );
+    }
     ctx.print_color_centered(
-        20,
+        33,
         RGB::named(rltk::MAGENTA),
         RGB::named(rltk::BLACK),
-        "Press any key to return to the menu.",
+        return_to_menu_prompt(),
+    );
+    closes_screen(ctx)
+}
+
+fn return_to_menu_prompt() -> String {
+    format!(
+        "Press {} or {} to return to the menu.",
+        keymap::describe(Action::MenuSelect),
+        keymap::describe(Action::MenuBack)
+    )
+}
+
+/// Whether the player has dismissed a screen that only shows something.
+fn closes_screen(ctx: &Rltk) -> bool {
+    matches!(
+        keymap::pressed(ctx, Context::Menu),
+        Some(Action::MenuSelect | Action::MenuBack)
+    )
+}
+
+/// What the help screen's legend explains, by look.
+const LEGEND: &[(&str, &str)] = &[
+    ("player", "You"),
+    ("monster.orc", "Orc"),
+    ("monster.goblin", "Goblin"),
+    ("vendor", "Quartermaster; walk into to trade"),
+    ("floor", "Floor"),
+    ("wall.ew", "Wall"),
+    ("stairs.down", "Stairs down"),
+    ("potion.health", "Health potion"),
+    ("medkit", "Medkit"),
+    ("food.rations", "Rations"),
+    ("scroll", "Scroll"),
+    ("device.plasma_cutter", "Device; uses charges"),
+    ("power_cell", "Power cell; recharges devices"),
+    ("weapon.dagger", "Weapon"),
+    ("shield", "Shield"),
+    ("credits", "Credits"),
+    ("container.crate", "Supply crate; walk into to open"),
+];
+
+/// Splits `text` into lines of at most `width` characters, between words.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Prints a heading and `text` below it in the help screen's right-hand column, returning the
+/// row after.
+fn help_note(ctx: &mut Rltk, y: i32, heading: &str, text: &str) -> i32 {
+    ctx.print_color(
+        45,
+        y,
+        RGB::named(rltk::CYAN),
+        RGB::named(rltk::BLACK),
+        heading,
+    );
+    let mut y = y + 1;
+    for line in wrap_text(text, 33) {
+        ctx.print_color(
+            46,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+        y += 1;
+    }
+    y + 1
+}
+
+/// Lists every command with the keys bound to it, explains what's on the map in the current
+/// theme, and how hunger and equipment work. Returns true once the player closes it.
+pub fn help_screen(ctx: &mut Rltk) -> bool {
+    let theme = theme::current();
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.print_color_centered(1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Help");
+
+    let mut y = 3;
+    for category in keymap::CATEGORIES.iter() {
+        ctx.print_color(
+            2,
+            y,
+            RGB::named(rltk::CYAN),
+            RGB::named(rltk::BLACK),
+            category,
+        );
+        y += 1;
+        for action in Action::ALL.iter().filter(|a| {
+            a.category() == *category && (**a != Action::WizardConsole || wizard::is_enabled())
+        }) {
+            ctx.print_color(
+                3,
+                y,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                format!("{:<24}{}", action.description(), keymap::describe(*action)),
+            );
+            y += 1;
+        }
+        y += 1;
+    }
+
+    ctx.print_color(
+        45,
+        3,
+        RGB::named(rltk::CYAN),
+        RGB::named(rltk::BLACK),
+        "Map",
+    );
+    for (i, (name, description)) in LEGEND.iter().enumerate() {
+        let y = 4 + i as i32;
+        let look = theme.look(name);
+        backend::set_layer(ctx, backend::MAP_LAYER);
+        ctx.set(
+            46,
+            y,
+            look.fg,
+            RGB::named(rltk::BLACK),
+            backend::glyph(look.glyph, theme.tileset),
+        );
+        backend::set_layer(ctx, backend::TEXT_LAYER);
+        ctx.print_color(
+            48,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            description,
+        );
+    }
+
+    let y = 5 + LEGEND.len() as i32;
+    let y = help_note(
+        ctx,
+        y,
+        "Hunger",
+        &format!(
+            "You grow hungrier as turns pass. While Hungry or Starving you can't recover health \
+             by waiting, and starving hurts every turn. Eat rations from the inventory ({}) to \
+             become Well Fed.",
+            keymap::describe(Action::Inventory)
+        ),
+    );
+    help_note(
+        ctx,
+        y,
+        "Equipment",
+        &format!(
+            "You have two slots, a melee weapon and a shield. Using one from the inventory \
+             equips it in place of whatever was in its slot, and {} takes it off. Cursed items \
+             stay on until a Remove Curse Scroll is read.",
+            keymap::describe(Action::Remove)
+        ),
+    );
+
+    ctx.print_color_centered(
+        48,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        format!(
+            "Press {} to return to the game",
+            keymap::describe(Action::MenuBack)
+        ),
     );
+    keymap::pressed(ctx, Context::Menu) == Some(Action::MenuBack)
+        || keymap::pressed(ctx, Context::Game) == Some(Action::Help)
+}
 
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConfirmResult {
+    NoResponse,
+    Yes,
+    No,
+}
+
+/// Asks `question` across the top of the screen, with the keys that answer it.
+pub fn confirm(ctx: &mut Rltk, question: &str) -> ConfirmResult {
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.print_color(
+        5,
+        0,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{} ({}/{})",
+            question,
+            keymap::describe(Action::MenuYes),
+            keymap::describe(Action::MenuNo)
+        ),
+    );
+    match keymap::pressed(ctx, Context::Menu) {
+        Some(Action::MenuYes) => ConfirmResult::Yes,
+        Some(Action::MenuNo | Action::MenuBack) => ConfirmResult::No,
+        _ => ConfirmResult::NoResponse,
+    }
+}
+
+/// A line of the options screen.
+#[derive(PartialEq, Copy, Clone)]
+pub enum OptionsEntry {
+    Scanlines,
+    FpsCap,
+    TileSize,
+    Theme,
+    Palette,
+    Cues,
+    Minimap,
+    Messages,
+    AutoPickup,
+    Confirmations,
+}
+
+const OPTIONS_ENTRIES: &[OptionsEntry] = &[
+    OptionsEntry::Scanlines,
+    OptionsEntry::FpsCap,
+    OptionsEntry::TileSize,
+    OptionsEntry::Theme,
+    OptionsEntry::Palette,
+    OptionsEntry::Cues,
+    OptionsEntry::Minimap,
+    OptionsEntry::Messages,
+    OptionsEntry::AutoPickup,
+    OptionsEntry::Confirmations,
+];
+
+/// The options that apply to how the game was built; the terminal has no scanlines and its
+/// tiles are whatever size the terminal's font is.
+fn options_entries() -> Vec<OptionsEntry> {
+    OPTIONS_ENTRIES
+        .iter()
+        .filter(|entry| {
+            !cfg!(feature = "terminal")
+                || !matches!(entry, OptionsEntry::Scanlines | OptionsEntry::TileSize)
+        })
+        .copied()
+        .collect()
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum OptionsMenuResult {
+    NoResponse { selection: usize },
+    Back,
+}
+
+fn on_off(setting: bool) -> String {
+    if setting { "On" } else { "Off" }.to_string()
+}
+
+fn change_option(ctx: &mut Rltk, entry: OptionsEntry, forward: bool) {
+    let current = theme::current();
+    let (themes, palettes) = (theme::theme_names(), theme::palette_names());
+    let changed = settings::update(|s| match entry {
+        OptionsEntry::Scanlines => s.scanlines = !s.scanlines,
+        OptionsEntry::FpsCap => {
+            s.fps_cap = settings::cycle(settings::FPS_CAPS, &s.fps_cap, forward)
+        }
+        OptionsEntry::TileSize => {
+            s.tile_size = settings::cycle(settings::TILE_SIZES, &s.tile_size, forward)
+        }
+        OptionsEntry::Theme => s.theme = Some(settings::cycle(&themes, &current.name, forward)),
+        OptionsEntry::Palette => s.palette = settings::cycle(&palettes, &current.palette, forward),
+        OptionsEntry::Cues => s.cues = !s.cues,
+        OptionsEntry::Minimap => s.minimap = !s.minimap,
+        OptionsEntry::Messages => {
+            s.messages = settings::cycle(settings::VERBOSITIES, &s.messages, forward)
+        }
+        OptionsEntry::AutoPickup => {
+            s.auto_pickup = settings::cycle(settings::AUTO_PICKUPS, &s.auto_pickup, forward)
+        }
+        OptionsEntry::Confirmations => s.confirmations = !s.confirmations,
+    });
+    backend::apply_settings(ctx, &changed);
+}
+
+pub fn options_menu(ctx: &mut Rltk, selection: usize) -> OptionsMenuResult {
+    let theme = theme::current();
+    let settings = settings::current();
+    let entries = options_entries();
+    ctx.print_color_centered(
+        15,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Options",
+    );
+    for (i, entry) in entries.iter().enumerate() {
+        let (label, value) = match entry {
+            OptionsEntry::Scanlines => ("Scanlines", on_off(settings.scanlines)),
+            OptionsEntry::FpsCap => ("Frame rate cap *", format!("{} fps", settings.fps_cap)),
+            OptionsEntry::TileSize => ("Tile size *", format!("{} px", settings.tile_size)),
+            OptionsEntry::Theme => ("Theme", theme.name.clone()),
+            OptionsEntry::Palette => ("Palette", theme.palette.clone()),
+            OptionsEntry::Cues => ("Non-colour cues", on_off(theme.cues)),
+            OptionsEntry::Minimap => ("Minimap", on_off(settings.minimap)),
+            OptionsEntry::Messages => ("Messages", settings.messages.label().to_string()),
+            OptionsEntry::AutoPickup => ("Auto-pickup", settings.auto_pickup.label().to_string()),
+            OptionsEntry::Confirmations => ("Confirmations", on_off(settings.confirmations)),
+        };
+        let fg = if i == selection {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color(
+            25,
+            18 + i as i32,
+            fg,
+            RGB::named(rltk::BLACK),
+            format!("{:<20}{}", label, value),
+        );
+    }
+    ctx.print_color_centered(
+        31,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        "* Takes effect the next time the game is started",
+    );
+    ctx.print_color_centered(
+        33,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{}, {} or {} to change, {} to go back",
+            keymap::describe(Action::MenuLeft),
+            keymap::describe(Action::MenuRight),
+            keymap::describe(Action::MenuSelect),
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+
+    let count = entries.len();
+    match keymap::pressed(ctx, Context::Menu) {
+        Some(Action::MenuBack) => OptionsMenuResult::Back,
+        Some(Action::MenuUp) => OptionsMenuResult::NoResponse {
+            selection: (selection + count - 1) % count,
+        },
+        Some(Action::MenuDown) => OptionsMenuResult::NoResponse {
+            selection: (selection + 1) % count,
+        },
+        Some(Action::MenuLeft) => {
+            change_option(ctx, entries[selection], false);
+            OptionsMenuResult::NoResponse { selection }
+        }
+        Some(Action::MenuRight) | Some(Action::MenuSelect) => {
+            change_option(ctx, entries[selection], true);
+            OptionsMenuResult::NoResponse { selection }
+        }
+        _ => OptionsMenuResult::NoResponse { selection },
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VendorMode {
+    Buy,
+    Sell,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VendorResult {
+    NoResponse,
+    Cancel,
+    Sell,
+    BuyItem,
+    ToggleMode,
+}
+
+fn draw_vendor_box(ctx: &mut Rltk, title: &str, credits_label: &str, lines: &[(String, String)]) {
+    let credits_colour = theme::current().look("ui.credits").fg;
+    let count = lines.len();
+    let y = (25 - (count / 2)) as i32;
+    let entries: Vec<String> = lines
+        .iter()
+        .map(|(name, price)| format!("{}  {}", name, price))
+        .collect();
+    let box_width = i32::max(
+        menu_box_width(&entries) + 4,
+        (title.len() + credits_label.len()) as i32 + 6,
+    );
+    let box_height = (count + 3) as i32;
+
+    backend::set_layer(ctx, backend::MAP_LAYER);
+    for dy in 0..=box_height {
+        for dx in 0..=box_width {
+            ctx.set(
+                15 + dx,
+                y - 2 + dy,
+                RGB::named(rltk::BLACK),
+                RGB::named(rltk::BLACK),
+                0,
+            );
+        }
+    }
+
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.draw_box(
+        15,
+        y - 2,
+        box_width,
+        box_height,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        title,
+    );
+    ctx.print_color(
+        15 + box_width - credits_label.len() as i32 - 1,
+        y - 2,
+        credits_colour,
+        RGB::named(rltk::BLACK),
+        credits_label,
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            "{} to switch, {} to cancel",
+            keymap::describe(Action::MenuSwitch),
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+
+    for (j, (name, price)) in lines.iter().enumerate() {
+        let line_y = y + j as i32;
+        ctx.set(
+            17,
+            line_y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            line_y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as rltk::FontCharType,
+        );
+        ctx.set(
+            19,
+            line_y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+        ctx.print(21, line_y, name);
+        ctx.print_color(
+            15 + box_width - price.len() as i32 - 2,
+            line_y,
+            credits_colour,
+            RGB::named(rltk::BLACK),
+            price,
+        );
+    }
+}
+
+fn vendor_selection(ctx: &mut Rltk, count: usize) -> Result<Option<usize>, VendorResult> {
+    match keymap::pressed(ctx, Context::Menu) {
+        Some(Action::MenuBack) => return Err(VendorResult::Cancel),
+        Some(Action::MenuSwitch) => return Err(VendorResult::ToggleMode),
+        _ => {}
+    }
     match ctx.key {
-        None => GameOverResult::NoSelection,
-        Some(_) => GameOverResult::QuitToMenu,
+        None => Err(VendorResult::NoResponse),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return Ok(Some(selection as usize));
+            }
+            Ok(None)
+        }
+    }
+}
+
+fn vendor_sell_menu(gs: &mut State, ctx: &mut Rltk) -> (VendorResult, Option<Entity>) {
+    let credits_label = format!(" {} credits ", vendor::player_credits(&gs.ecs));
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let obfuscated_names = gs.ecs.read_storage::<ObfuscatedName>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let items = gs.ecs.read_storage::<Item>();
+    let entities = gs.ecs.entities();
+
+    let mut for_sale: Vec<(Entity, String, String)> = Vec::new();
+    for (entity, pack, item) in (&entities, &backpack, &items).join() {
+        if pack.owner == *player_entity {
+            for_sale.push((
+                entity,
+                get_item_display_name(&names, &obfuscated_names, entity),
+                format!("{} cr", vendor::sell_price(item)),
+            ));
+        }
+    }
+    for_sale.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let lines: Vec<(String, String)> = for_sale
+        .iter()
+        .map(|(_entity, name, price)| (name.clone(), price.clone()))
+        .collect();
+    draw_vendor_box(ctx, "Sell Items", &credits_label, &lines);
+
+    match vendor_selection(ctx, for_sale.len()) {
+        Err(result) => (result, None),
+        Ok(None) => (VendorResult::NoResponse, None),
+        Ok(Some(selection)) => (VendorResult::Sell, Some(for_sale[selection].0)),
+    }
+}
+
+fn vendor_buy_menu(gs: &mut State, ctx: &mut Rltk) -> (VendorResult, Option<usize>) {
+    let credits_label = format!(" {} credits ", vendor::player_credits(&gs.ecs));
+    let lines: Vec<(String, String)> = vendor::STOCK
+        .iter()
+        .map(|(name, price)| (name.to_string(), format!("{} cr", price)))
+        .collect();
+    draw_vendor_box(ctx, "Buy Items", &credits_label, &lines);
+
+    match vendor_selection(ctx, vendor::STOCK.len()) {
+        Err(result) => (result, None),
+        Ok(None) => (VendorResult::NoResponse, None),
+        Ok(Some(selection)) => (VendorResult::BuyItem, Some(selection)),
+    }
+}
+
+pub fn show_vendor(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    mode: VendorMode,
+) -> (VendorResult, Option<Entity>, Option<usize>) {
+    match mode {
+        VendorMode::Buy => {
+            let (result, stock) = vendor_buy_menu(gs, ctx);
+            (result, None, stock)
+        }
+        VendorMode::Sell => {
+            let (result, item) = vendor_sell_menu(gs, ctx);
+            (result, item, None)
+        }
     }
 }
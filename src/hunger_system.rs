@@ -1,4 +1,8 @@
-use super::{HungerClock, HungerState, RunState, SufferDamage, gamelog::GameLog};
+use super::{
+    Equipped, HungerClock, HungerState, RunState, SlowsHunger, SufferDamage, gamelog::GameLog,
+    run_info::RunInfo,
+};
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 
 pub struct HungerSystem {}
@@ -11,13 +15,27 @@ impl<'a> System<'a> for HungerSystem {
         ReadExpect<'a, RunState>,
         WriteStorage<'a, SufferDamage>,
         WriteExpect<'a, GameLog>,
+        ReadStorage<'a, SlowsHunger>,
+        ReadStorage<'a, Equipped>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, RunInfo>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut hunger_clock, player_entity, runstate, mut inflict_damage, mut log) =
-            data;
+        let (
+            entities,
+            mut hunger_clock,
+            player_entity,
+            runstate,
+            mut inflict_damage,
+            mut log,
+            slows_hunger,
+            equipped,
+            mut rng,
+            mut run_info,
+        ) = data;
 
-        for (entity, mut clock) in (&entities, &mut hunger_clock).join() {
+        for (entity, clock) in (&entities, &mut hunger_clock).join() {
             let mut proceed = false;
 
             match *runstate {
@@ -35,6 +53,16 @@ impl<'a> System<'a> for HungerSystem {
             }
 
             if proceed {
+                let mut slowed_percent = 0;
+                for (slows, equipped_by) in (&slows_hunger, &equipped).join() {
+                    if equipped_by.owner == entity {
+                        slowed_percent += slows.percent;
+                    }
+                }
+                if slowed_percent > 0 && rng.roll_dice(1, 100) <= slowed_percent {
+                    continue;
+                }
+
                 clock.duration -= 1;
                 if clock.duration < 1 {
                     match clock.state {
@@ -42,7 +70,7 @@ impl<'a> System<'a> for HungerSystem {
                             clock.state = HungerState::Normal;
                             clock.duration = 200;
                             if entity == *player_entity {
-                                log.entries.push("You are no longer well fed.".to_string());
+                                log.detail("You are no longer well fed.".to_string());
                             }
                         }
                         HungerState::Normal => {
@@ -63,6 +91,7 @@ impl<'a> System<'a> for HungerSystem {
                             if entity == *player_entity {
                                 log.entries
                                     .push("Your hunger pangs are getting painful!".to_string());
+                                run_info.last_damaged_by = Some("starvation".to_string());
                             }
                             SufferDamage::new_damage(&mut inflict_damage, entity, 1);
                         }
@@ -0,0 +1,143 @@
+use super::{IdentifiedItem, Name, ObfuscatedName, gamelog::GameLog, spawner};
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+
+const CHIP_DESCRIPTORS: &[&str] = &[
+    "cracked",
+    "glowing",
+    "scorched",
+    "humming",
+    "etched",
+    "flickering",
+    "encrypted",
+    "corroded",
+];
+
+const VIAL_DESCRIPTORS: &[&str] = &[
+    "fizzing",
+    "cloudy",
+    "luminous",
+    "viscous",
+    "smoking",
+    "iridescent",
+    "bubbling",
+    "murky",
+];
+
+/// Tracks which magic items the player has learned to recognise during this run,
+/// along with the randomised alias each unknown item type is shown under.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct IdentificationMap {
+    pub identified_items: HashSet<String>,
+    pub aliases: HashMap<String, String>,
+}
+
+impl IdentificationMap {
+    pub fn new(rng: &mut RandomNumberGenerator) -> IdentificationMap {
+        let mut identification = IdentificationMap::default();
+        identification.assign_aliases(spawner::SCROLL_NAMES, "Data Chip", CHIP_DESCRIPTORS, rng);
+        identification.assign_aliases(spawner::POTION_NAMES, "Vial", VIAL_DESCRIPTORS, rng);
+        identification
+    }
+
+    fn assign_aliases(
+        &mut self,
+        names: &[&str],
+        base: &str,
+        descriptors: &[&str],
+        rng: &mut RandomNumberGenerator,
+    ) {
+        let mut available = descriptors.to_vec();
+        for name in names.iter() {
+            let roll = rng.roll_dice(1, available.len() as i32) - 1;
+            let descriptor = available.remove(roll as usize);
+            self.aliases
+                .insert(name.to_string(), format!("{} ({})", base, descriptor));
+        }
+    }
+
+    /// Returns the alias an item should be shown under, or `None` if it is already known.
+    pub fn obfuscated_name(&self, real_name: &str) -> Option<String> {
+        if self.identified_items.contains(real_name) {
+            return None;
+        }
+        self.aliases.get(real_name).cloned()
+    }
+}
+
+pub fn get_item_display_name<D>(
+    names: &ReadStorage<Name>,
+    obfuscated_names: &Storage<ObfuscatedName, D>,
+    item: Entity,
+) -> String
+where
+    D: Deref<Target = MaskedStorage<ObfuscatedName>>,
+{
+    if let Some(obfuscated) = obfuscated_names.get(item) {
+        return obfuscated.name.clone();
+    }
+    if let Some(name) = names.get(item) {
+        return name.name.clone();
+    }
+    "Unknown item".to_string()
+}
+
+pub struct ItemIdentificationSystem {}
+
+impl<'a> System<'a> for ItemIdentificationSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        WriteExpect<'a, IdentificationMap>,
+        Entities<'a>,
+        WriteStorage<'a, IdentifiedItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, ObfuscatedName>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut log,
+            mut identification,
+            entities,
+            mut identified,
+            names,
+            mut obfuscated_names,
+        ) = data;
+
+        for (entity, id) in (&entities, &identified).join() {
+            if entity != *player_entity {
+                continue;
+            }
+            for name in id.names.iter() {
+                if !identification.identified_items.insert(name.clone()) {
+                    continue;
+                }
+                if let Some(alias) = identification.aliases.get(name) {
+                    log.entries
+                        .push(format!("You recognise the {} as a {}.", alias, name));
+                }
+
+                let mut to_reveal: Vec<Entity> = Vec::new();
+                for (item_entity, item_name, _obfuscated) in
+                    (&entities, &names, &obfuscated_names).join()
+                {
+                    if item_name.name == *name {
+                        to_reveal.push(item_entity);
+                    }
+                }
+                for item in to_reveal.iter() {
+                    obfuscated_names.remove(*item);
+                }
+            }
+        }
+
+        identified.clear();
+    }
+}
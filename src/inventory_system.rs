@@ -1,23 +1,35 @@
 use super::{
-    AreaOfEffect, CombatStats, Confusion, Consumable, Equippable, Equipped, HungerClock,
-    HungerState, InBackpack, InflictsDamage, Map, Name, Position, ProvidesFood, ProvidesHealing,
-    SufferDamage, WantsToDropItem, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
+    AreaOfEffect, Charges, CombatStats, Confusion, Consumable, Currency, CursedItem, Equippable,
+    Equipped, HungerClock, HungerState, IdentifiedItem, InBackpack, InflictsDamage, Item,
+    LootContainer, Map, Name, ObfuscatedName, Position, ProvidesFood, ProvidesHealing,
+    ProvidesIdentification, ProvidesRecharge, ProvidesRemoveCurse, SufferDamage, Viewshed, Wallet,
+    WantsToDropItem, WantsToOpenContainer, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
     gamelog::GameLog,
 };
+use crate::encumbrance_system::{OVERLOAD_MULTIPLIER, carried_weight, carry_capacity};
+use crate::identification_system::get_item_display_name;
 use crate::particle_system::ParticleBuilder;
+use crate::run_info::RunInfo;
 use specs::prelude::*;
 
 pub struct ItemCollectionSystem {}
 
 impl<'a> System<'a> for ItemCollectionSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
         ReadExpect<'a, Entity>,
         WriteExpect<'a, GameLog>,
         WriteStorage<'a, WantsToPickupItem>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
+        ReadStorage<'a, ObfuscatedName>,
         WriteStorage<'a, InBackpack>,
         Entities<'a>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Currency>,
+        WriteStorage<'a, Wallet>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -27,11 +39,50 @@ impl<'a> System<'a> for ItemCollectionSystem {
             mut wants_pickup,
             mut positions,
             names,
+            obfuscated_names,
             mut backpack,
             entities,
+            items,
+            equipped,
+            combat_stats,
+            currency,
+            mut wallets,
         ) = data;
 
         for (_entity, pickup) in (&entities, &wants_pickup).join() {
+            // Credits go straight into the wallet rather than the backpack
+            if let (Some(credits), Some(wallet)) = (
+                currency.get(pickup.item),
+                wallets.get_mut(pickup.collected_by),
+            ) {
+                wallet.credits += credits.amount;
+                if pickup.collected_by == *player_entity {
+                    game_log
+                        .entries
+                        .push(format!("You pick up {} credits.", credits.amount));
+                }
+                entities
+                    .delete(pickup.item)
+                    .expect("Unable to delete credits");
+                continue;
+            }
+
+            if let (Some(stats), Some(item)) = (
+                combat_stats.get(pickup.collected_by),
+                items.get(pickup.item),
+            ) {
+                let weight = carried_weight(pickup.collected_by, &items, &backpack, &equipped);
+                if weight + item.weight > carry_capacity(stats) * OVERLOAD_MULTIPLIER {
+                    if pickup.collected_by == *player_entity {
+                        game_log.entries.push(format!(
+                            "The {} is too heavy for you to carry.",
+                            get_item_display_name(&names, &obfuscated_names, pickup.item)
+                        ));
+                    }
+                    continue;
+                }
+            }
+
             positions.remove(pickup.item);
             backpack
                 .insert(
@@ -45,7 +96,7 @@ impl<'a> System<'a> for ItemCollectionSystem {
             if pickup.collected_by == *player_entity {
                 game_log.entries.push(format!(
                     "You pick up the {}.",
-                    names.get(pickup.item).unwrap().name
+                    get_item_display_name(&names, &obfuscated_names, pickup.item)
                 ));
             }
         }
@@ -78,6 +129,19 @@ impl<'a> System<'a> for ItemUseSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, ProvidesFood>,
         WriteStorage<'a, HungerClock>,
+        WriteStorage<'a, ObfuscatedName>,
+        WriteStorage<'a, IdentifiedItem>,
+        ReadStorage<'a, ProvidesIdentification>,
+        (
+            WriteStorage<'a, CursedItem>,
+            ReadStorage<'a, ProvidesRemoveCurse>,
+        ),
+        WriteStorage<'a, Viewshed>,
+        (
+            WriteStorage<'a, Charges>,
+            ReadStorage<'a, ProvidesRecharge>,
+            WriteExpect<'a, RunInfo>,
+        ),
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -102,10 +166,41 @@ impl<'a> System<'a> for ItemUseSystem {
             positions,
             provides_food,
             mut hunger_clock,
+            mut obfuscated_names,
+            mut identified,
+            provides_identification,
+            (mut cursed, provides_remove_curse),
+            mut viewsheds,
+            (mut charges, provides_recharge, mut run_info),
         ) = data;
 
         for (entity, use_item) in (&entities, &wants_use).join() {
             let mut used_item = true;
+            let item_name = get_item_display_name(&names, &obfuscated_names, use_item.item);
+
+            if charges
+                .get(use_item.item)
+                .is_some_and(|charge| charge.current < 1)
+            {
+                if entity == *player_entity {
+                    game_log
+                        .entries
+                        .push(format!("The {} is out of charge.", item_name));
+                }
+                continue;
+            }
+
+            // Equipment is identified piece by piece when equipped, rather than by type
+            if entity == *player_entity
+                && obfuscated_names.get(use_item.item).is_some()
+                && equippable.get(use_item.item).is_none()
+            {
+                IdentifiedItem::new_identification(
+                    &mut identified,
+                    entity,
+                    &names.get(use_item.item).unwrap().name,
+                );
+            }
 
             let mut targets: Vec<Entity> = Vec::new();
             match use_item.target {
@@ -135,9 +230,7 @@ impl<'a> System<'a> for ItemUseSystem {
                                 particle_builder.request(
                                     tile_idx.x,
                                     tile_idx.y,
-                                    rltk::RGB::named(rltk::ORANGE),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    617,
+                                    "particle.explosion",
                                     200.0,
                                 )
                             }
@@ -154,39 +247,65 @@ impl<'a> System<'a> for ItemUseSystem {
                     let target = targets[0];
 
                     let mut to_unequip: Vec<Entity> = Vec::new();
-                    for (item_entity, already_equipped, name) in
-                        (&entities, &equipped, &names).join()
-                    {
+                    let mut stuck_with: Option<Entity> = None;
+                    for (item_entity, already_equipped) in (&entities, &equipped).join() {
                         if already_equipped.owner == target && already_equipped.slot == target_slot
                         {
+                            if cursed.get(item_entity).is_some() {
+                                stuck_with = Some(item_entity);
+                            }
                             to_unequip.push(item_entity);
+                        }
+                    }
+
+                    if let Some(stuck_with) = stuck_with {
+                        if target == *player_entity {
+                            game_log.entries.push(format!(
+                                "You cannot take off the {}; it is cursed!",
+                                get_item_display_name(&names, &obfuscated_names, stuck_with)
+                            ));
+                        }
+                    } else {
+                        for item in to_unequip.iter() {
+                            equipped.remove(*item);
+                            backpack
+                                .insert(*item, InBackpack { owner: target })
+                                .expect("Unable to insert backpack entry");
                             if target == *player_entity {
-                                game_log.entries.push(format!("You unequip {}.", name.name));
+                                game_log.entries.push(format!(
+                                    "You unequip {}.",
+                                    get_item_display_name(&names, &obfuscated_names, *item)
+                                ));
                             }
                         }
-                    }
-                    for item in to_unequip.iter() {
-                        equipped.remove(*item);
-                        backpack
-                            .insert(*item, InBackpack { owner: target })
-                            .expect("Unable to insert backpack entry");
-                    }
 
-                    equipped
-                        .insert(
-                            use_item.item,
-                            Equipped {
-                                owner: target,
-                                slot: target_slot,
-                            },
-                        )
-                        .expect("Unable to insert equipped component");
-                    backpack.remove(use_item.item);
-                    if target == *player_entity {
-                        game_log.entries.push(format!(
-                            "You equip {}.",
-                            names.get(use_item.item).unwrap().name
-                        ));
+                        equipped
+                            .insert(
+                                use_item.item,
+                                Equipped {
+                                    owner: target,
+                                    slot: target_slot,
+                                },
+                            )
+                            .expect("Unable to insert equipped component");
+                        backpack.remove(use_item.item);
+                        if let Some(viewshed) = viewsheds.get_mut(target) {
+                            viewshed.dirty = true;
+                        }
+                        if target == *player_entity {
+                            game_log.entries.push(format!("You equip {}.", item_name));
+                            if obfuscated_names.remove(use_item.item).is_some() {
+                                game_log.entries.push(format!(
+                                    "It is a {}.",
+                                    names.get(use_item.item).unwrap().name
+                                ));
+                            }
+                            if cursed.get(use_item.item).is_some() {
+                                game_log
+                                    .entries
+                                    .push("It clamps onto you - it is cursed!".to_string());
+                            }
+                        }
                     }
                 }
             }
@@ -202,21 +321,13 @@ impl<'a> System<'a> for ItemUseSystem {
                             if entity == *player_entity {
                                 game_log.entries.push(format!(
                                     "You use the {}, healing {} hp.",
-                                    names.get(use_item.item).unwrap().name,
-                                    healer.heal_amount
+                                    item_name, healer.heal_amount
                                 ));
                             }
 
                             let pos = positions.get(*target);
                             if let Some(pos) = pos {
-                                particle_builder.request(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::GREEN),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    529,
-                                    200.0,
-                                );
+                                particle_builder.request(pos.x, pos.y, "particle.heal", 200.0);
                             }
                         }
 
@@ -232,24 +343,21 @@ impl<'a> System<'a> for ItemUseSystem {
                     used_item = false;
                     for mob in targets.iter() {
                         SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
+                        if *mob == *player_entity {
+                            run_info.last_damaged_by = Some(item_name.clone());
+                        } else if entity == *player_entity {
+                            run_info.damage_dealt += damage.damage;
+                        }
                         if entity == *player_entity {
                             let mob_name = names.get(*mob).unwrap();
-                            let item_name = names.get(use_item.item).unwrap();
                             game_log.entries.push(format!(
                                 "You use {} on {}, inflicting {} hp.",
-                                item_name.name, mob_name.name, damage.damage
+                                item_name, mob_name.name, damage.damage
                             ));
 
                             let pos = positions.get(*mob);
                             if let Some(pos) = pos {
-                                particle_builder.request(
-                                    pos.x,
-                                    pos.y,
-                                    rltk::RGB::named(rltk::RED),
-                                    rltk::RGB::named(rltk::BLACK),
-                                    575,
-                                    200.0,
-                                )
+                                particle_builder.request(pos.x, pos.y, "particle.damage", 200.0)
                             }
                         }
 
@@ -268,10 +376,116 @@ impl<'a> System<'a> for ItemUseSystem {
                     if let Some(hc) = hc {
                         hc.state = HungerState::WellFed;
                         hc.duration = 20;
-                        game_log.entries.push(format!(
-                            "You eat the {}.",
-                            names.get(use_item.item).unwrap().name
-                        ));
+                        game_log.entries.push(format!("You eat the {}.", item_name));
+                    }
+                }
+            }
+
+            let item_identifies = provides_identification.get(use_item.item);
+            match item_identifies {
+                None => {}
+                Some(_) => {
+                    used_item = true;
+                    let target = targets[0];
+                    let mut to_reveal: Vec<Entity> = Vec::new();
+                    for (item_entity, pack, _obfuscated) in
+                        (&entities, &backpack, &obfuscated_names).join()
+                    {
+                        if pack.owner == target && item_entity != use_item.item {
+                            to_reveal.push(item_entity);
+                        }
+                    }
+                    let revealed = to_reveal.len();
+                    for item in to_reveal.iter() {
+                        if equippable.get(*item).is_some() {
+                            obfuscated_names.remove(*item);
+                        } else {
+                            IdentifiedItem::new_identification(
+                                &mut identified,
+                                target,
+                                &names.get(*item).unwrap().name,
+                            );
+                        }
+                    }
+                    if target == *player_entity {
+                        if revealed > 0 {
+                            game_log.entries.push(format!(
+                                "You run the {}, and it analyses your pack.",
+                                item_name
+                            ));
+                        } else {
+                            game_log.entries.push(format!(
+                                "You run the {}, but you carry nothing unknown.",
+                                item_name
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let item_uncurses = provides_remove_curse.get(use_item.item);
+            match item_uncurses {
+                None => {}
+                Some(_) => {
+                    used_item = true;
+                    let target = targets[0];
+                    let mut to_uncurse: Vec<Entity> = Vec::new();
+                    for (item_entity, _cursed) in (&entities, &cursed).join() {
+                        let is_worn = equipped
+                            .get(item_entity)
+                            .is_some_and(|worn| worn.owner == target);
+                        let is_carried = backpack
+                            .get(item_entity)
+                            .is_some_and(|pack| pack.owner == target);
+                        if is_worn || is_carried {
+                            to_uncurse.push(item_entity);
+                        }
+                    }
+                    for item in to_uncurse.iter() {
+                        cursed.remove(*item);
+                    }
+                    if target == *player_entity {
+                        if to_uncurse.is_empty() {
+                            game_log.entries.push(format!(
+                                "You run the {}, but nothing seems to happen.",
+                                item_name
+                            ));
+                        } else {
+                            game_log.entries.push(format!(
+                                "You run the {}, and the malevolent hold on your gear fades.",
+                                item_name
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let item_recharges = provides_recharge.get(use_item.item);
+            match item_recharges {
+                None => {}
+                Some(_) => {
+                    used_item = true;
+                    let target = targets[0];
+                    let mut recharged = 0;
+                    for (_item_entity, pack, charge) in (&entities, &backpack, &mut charges).join()
+                    {
+                        if pack.owner == target && charge.current < charge.max {
+                            charge.current = charge.max;
+                            recharged += 1;
+                        }
+                    }
+                    if target == *player_entity {
+                        if recharged > 0 {
+                            game_log.entries.push(format!(
+                                "You slot the {}, recharging {} device(s).",
+                                item_name, recharged
+                            ));
+                        } else {
+                            game_log.entries.push(format!(
+                                "You slot the {}, but nothing needs charging.",
+                                item_name
+                            ));
+                        }
                     }
                 }
             }
@@ -287,10 +501,9 @@ impl<'a> System<'a> for ItemUseSystem {
                             add_confusion.push((*mob, confusion.turns));
                             if entity == *player_entity {
                                 let mob_name = names.get(*mob).unwrap();
-                                let item_name = names.get(use_item.item).unwrap();
                                 game_log.entries.push(format!(
                                     "You use {} on {}, confusing them.",
-                                    item_name.name, mob_name.name
+                                    item_name, mob_name.name
                                 ));
 
                                 let pos = positions.get(*mob);
@@ -298,9 +511,7 @@ impl<'a> System<'a> for ItemUseSystem {
                                     particle_builder.request(
                                         pos.x,
                                         pos.y,
-                                        rltk::RGB::named(rltk::MAGENTA),
-                                        rltk::RGB::named(rltk::BLACK),
-                                        574,
+                                        "particle.confusion",
                                         200.0,
                                     )
                                 }
@@ -318,6 +529,18 @@ impl<'a> System<'a> for ItemUseSystem {
             }
 
             if used_item {
+                if entity == *player_entity {
+                    run_info.items_used += 1;
+                }
+                if let Some(charge) = charges.get_mut(use_item.item) {
+                    charge.current -= 1;
+                    if charge.current < 1 && entity == *player_entity {
+                        game_log
+                            .entries
+                            .push(format!("The {} is drained.", item_name));
+                    }
+                }
+
                 let consumable = consumables.get(use_item.item);
                 match consumable {
                     None => {}
@@ -340,6 +563,7 @@ impl<'a> System<'a> for ItemDropSystem {
         Entities<'a>,
         WriteStorage<'a, WantsToDropItem>,
         ReadStorage<'a, Name>,
+        ReadStorage<'a, ObfuscatedName>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, InBackpack>,
     );
@@ -351,6 +575,7 @@ impl<'a> System<'a> for ItemDropSystem {
             entities,
             mut wants_drop,
             names,
+            obfuscated_names,
             mut positions,
             mut backpack,
         ) = data;
@@ -375,7 +600,7 @@ impl<'a> System<'a> for ItemDropSystem {
             if entity == *player_entity {
                 game_log.entries.push(format!(
                     "You drop the {}.",
-                    names.get(to_drop.item).unwrap().name
+                    get_item_display_name(&names, &obfuscated_names, to_drop.item)
                 ));
             }
         }
@@ -387,22 +612,91 @@ pub struct ItemRemoveSystem {}
 impl<'a> System<'a> for ItemRemoveSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
         Entities<'a>,
         WriteStorage<'a, WantsToRemoveItem>,
         WriteStorage<'a, Equipped>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, CursedItem>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, ObfuscatedName>,
+        WriteStorage<'a, Viewshed>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut wants_remove, mut equipped, mut backpack) = data;
+        let (
+            player_entity,
+            mut game_log,
+            entities,
+            mut wants_remove,
+            mut equipped,
+            mut backpack,
+            cursed,
+            names,
+            obfuscated_names,
+            mut viewsheds,
+        ) = data;
 
         for (entity, to_remove) in (&entities, &wants_remove).join() {
+            if cursed.get(to_remove.item).is_some() {
+                if entity == *player_entity {
+                    game_log.entries.push(format!(
+                        "You cannot remove the {}; it is cursed!",
+                        get_item_display_name(&names, &obfuscated_names, to_remove.item)
+                    ));
+                }
+                continue;
+            }
             equipped.remove(to_remove.item);
             backpack
                 .insert(to_remove.item, InBackpack { owner: entity })
                 .expect("Unable to insert backpack");
+            if let Some(viewshed) = viewsheds.get_mut(entity) {
+                viewshed.dirty = true;
+            }
         }
 
         wants_remove.clear();
     }
 }
+
+pub struct ContainerSystem {}
+
+impl<'a> System<'a> for ContainerSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToOpenContainer>,
+        ReadStorage<'a, LootContainer>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, Wallet>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, mut game_log, entities, mut wants_open, containers, names, mut wallets) =
+            data;
+
+        for (entity, open) in (&entities, &wants_open).join() {
+            if let (Some(container), Some(wallet)) =
+                (containers.get(open.container), wallets.get_mut(entity))
+            {
+                wallet.credits += container.credits;
+                if entity == *player_entity {
+                    game_log.entries.push(format!(
+                        "You pry open the {} and find {} credits.",
+                        names.get(open.container).unwrap().name,
+                        container.credits
+                    ));
+                }
+                entities
+                    .delete(open.container)
+                    .expect("Unable to delete container");
+            }
+        }
+
+        wants_open.clear();
+    }
+}
@@ -0,0 +1,556 @@
+//! What each key does. Every key press is looked up here as an `Action`, so the bindings can
+//! be changed with a `keymap.json` in the data directory, which maps actions to lists of keys
+//! such as `"K"`, `"Numpad8"` or `"Shift+."`. Actions it doesn't mention keep their default
+//! keys.
+
+use super::saveload_system;
+use rltk::{Rltk, VirtualKeyCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::sync::LazyLock;
+
+/// Where a key press is read. The same key can do different things in each.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Context {
+    /// Playing, on the map.
+    Game,
+    /// Any menu or screen over the map.
+    Menu,
+    /// Watching a replay.
+    Replay,
+}
+
+/// The headings actions are grouped under on the help screen, in order.
+pub const CATEGORIES: &[&str] = &["Movement", "Items", "Game", "Menus", "Replays"];
+
+#[derive(Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    PickUp,
+    Descend,
+    Inventory,
+    Drop,
+    Remove,
+    Save,
+    SwitchTheme,
+    Overview,
+    Help,
+    WizardConsole,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    MenuSelect,
+    MenuBack,
+    MenuDelete,
+    MenuSwitch,
+    MenuYes,
+    MenuNo,
+    ReplayPause,
+    ReplayFastForward,
+    ReplayLeave,
+}
+
+impl Action {
+    /// Every action, in the order the help screen lists them.
+    pub const ALL: &[Action] = &[
+        Action::MoveNorth,
+        Action::MoveSouth,
+        Action::MoveWest,
+        Action::MoveEast,
+        Action::MoveNorthWest,
+        Action::MoveNorthEast,
+        Action::MoveSouthWest,
+        Action::MoveSouthEast,
+        Action::Wait,
+        Action::PickUp,
+        Action::Descend,
+        Action::Inventory,
+        Action::Drop,
+        Action::Remove,
+        Action::Save,
+        Action::SwitchTheme,
+        Action::Overview,
+        Action::Help,
+        Action::WizardConsole,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLeft,
+        Action::MenuRight,
+        Action::MenuSelect,
+        Action::MenuBack,
+        Action::MenuDelete,
+        Action::MenuSwitch,
+        Action::MenuYes,
+        Action::MenuNo,
+        Action::ReplayPause,
+        Action::ReplayFastForward,
+        Action::ReplayLeave,
+    ];
+
+    /// The heading the help screen lists this under.
+    pub fn category(self) -> &'static str {
+        match self {
+            Action::MoveNorth
+            | Action::MoveSouth
+            | Action::MoveWest
+            | Action::MoveEast
+            | Action::MoveNorthWest
+            | Action::MoveNorthEast
+            | Action::MoveSouthWest
+            | Action::MoveSouthEast
+            | Action::Wait => "Movement",
+            Action::PickUp | Action::Inventory | Action::Drop | Action::Remove => "Items",
+            Action::Descend
+            | Action::Save
+            | Action::SwitchTheme
+            | Action::Overview
+            | Action::Help
+            | Action::WizardConsole => "Game",
+            Action::ReplayPause | Action::ReplayFastForward | Action::ReplayLeave => "Replays",
+            _ => "Menus",
+        }
+    }
+
+    pub fn context(self) -> Context {
+        match self {
+            Action::MenuUp
+            | Action::MenuDown
+            | Action::MenuLeft
+            | Action::MenuRight
+            | Action::MenuSelect
+            | Action::MenuBack
+            | Action::MenuDelete
+            | Action::MenuSwitch
+            | Action::MenuYes
+            | Action::MenuNo => Context::Menu,
+            Action::ReplayPause | Action::ReplayFastForward | Action::ReplayLeave => {
+                Context::Replay
+            }
+            _ => Context::Game,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveNorth => "Move/attack north",
+            Action::MoveSouth => "Move/attack south",
+            Action::MoveWest => "Move/attack west",
+            Action::MoveEast => "Move/attack east",
+            Action::MoveNorthWest => "Move/attack north-west",
+            Action::MoveNorthEast => "Move/attack north-east",
+            Action::MoveSouthWest => "Move/attack south-west",
+            Action::MoveSouthEast => "Move/attack south-east",
+            Action::Wait => "Wait a turn",
+            Action::PickUp => "Pick up an item",
+            Action::Descend => "Descend the stairs",
+            Action::Inventory => "Use an item",
+            Action::Drop => "Drop an item",
+            Action::Remove => "Remove equipment",
+            Action::Save => "Save the game",
+            Action::SwitchTheme => "Switch theme",
+            Action::Overview => "Show the whole level",
+            Action::Help => "Show this help",
+            Action::WizardConsole => "Wizard console",
+            Action::MenuUp => "Previous entry",
+            Action::MenuDown => "Next entry",
+            Action::MenuLeft => "Change option back",
+            Action::MenuRight => "Change option",
+            Action::MenuSelect => "Choose",
+            Action::MenuBack => "Close the menu",
+            Action::MenuDelete => "Erase a save",
+            Action::MenuSwitch => "Switch buying/selling",
+            Action::MenuYes => "Answer yes",
+            Action::MenuNo => "Answer no",
+            Action::ReplayPause => "Pause or resume",
+            Action::ReplayFastForward => "Fast-forward",
+            Action::ReplayLeave => "Stop watching",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::MoveNorth => &["Up", "Numpad8", "K"],
+            Action::MoveSouth => &["Down", "Numpad2", "J"],
+            Action::MoveWest => &["Left", "Numpad4", "H"],
+            Action::MoveEast => &["Right", "Numpad6", "L"],
+            Action::MoveNorthWest => &["Numpad7", "Y"],
+            Action::MoveNorthEast => &["Numpad9", "U"],
+            Action::MoveSouthWest => &["Numpad1", "B"],
+            Action::MoveSouthEast => &["Numpad3", "N"],
+            Action::Wait => &["Numpad5", "Space"],
+            Action::PickUp => &["G"],
+            Action::Descend => &["Shift+."],
+            Action::Inventory => &["I"],
+            Action::Drop => &["D"],
+            Action::Remove => &["R"],
+            Action::Save => &["Escape"],
+            Action::SwitchTheme => &["T"],
+            Action::Overview => &["M"],
+            Action::Help => &["Shift+/", "F1"],
+            Action::WizardConsole => &["`"],
+            Action::MenuUp => &["Up", "Numpad8", "K"],
+            Action::MenuDown => &["Down", "Numpad2", "J"],
+            Action::MenuLeft => &["Left", "Numpad4", "H"],
+            Action::MenuRight => &["Right", "Numpad6", "L"],
+            Action::MenuSelect => &["Enter"],
+            Action::MenuBack => &["Escape"],
+            Action::MenuDelete => &["Delete"],
+            Action::MenuSwitch => &["Space"],
+            Action::MenuYes => &["Y"],
+            Action::MenuNo => &["N"],
+            Action::ReplayPause => &["Space"],
+            Action::ReplayFastForward => &["F"],
+            Action::ReplayLeave => &["Escape"],
+        }
+    }
+}
+
+/// The keys that can be bound, by the names used for them in the keymap.
+const KEYS: &[(&str, VirtualKeyCode)] = &[
+    ("A", VirtualKeyCode::A),
+    ("B", VirtualKeyCode::B),
+    ("C", VirtualKeyCode::C),
+    ("D", VirtualKeyCode::D),
+    ("E", VirtualKeyCode::E),
+    ("F", VirtualKeyCode::F),
+    ("G", VirtualKeyCode::G),
+    ("H", VirtualKeyCode::H),
+    ("I", VirtualKeyCode::I),
+    ("J", VirtualKeyCode::J),
+    ("K", VirtualKeyCode::K),
+    ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M),
+    ("N", VirtualKeyCode::N),
+    ("O", VirtualKeyCode::O),
+    ("P", VirtualKeyCode::P),
+    ("Q", VirtualKeyCode::Q),
+    ("R", VirtualKeyCode::R),
+    ("S", VirtualKeyCode::S),
+    ("T", VirtualKeyCode::T),
+    ("U", VirtualKeyCode::U),
+    ("V", VirtualKeyCode::V),
+    ("W", VirtualKeyCode::W),
+    ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y),
+    ("Z", VirtualKeyCode::Z),
+    ("0", VirtualKeyCode::Key0),
+    ("1", VirtualKeyCode::Key1),
+    ("2", VirtualKeyCode::Key2),
+    ("3", VirtualKeyCode::Key3),
+    ("4", VirtualKeyCode::Key4),
+    ("5", VirtualKeyCode::Key5),
+    ("6", VirtualKeyCode::Key6),
+    ("7", VirtualKeyCode::Key7),
+    ("8", VirtualKeyCode::Key8),
+    ("9", VirtualKeyCode::Key9),
+    ("Numpad0", VirtualKeyCode::Numpad0),
+    ("Numpad1", VirtualKeyCode::Numpad1),
+    ("Numpad2", VirtualKeyCode::Numpad2),
+    ("Numpad3", VirtualKeyCode::Numpad3),
+    ("Numpad4", VirtualKeyCode::Numpad4),
+    ("Numpad5", VirtualKeyCode::Numpad5),
+    ("Numpad6", VirtualKeyCode::Numpad6),
+    ("Numpad7", VirtualKeyCode::Numpad7),
+    ("Numpad8", VirtualKeyCode::Numpad8),
+    ("Numpad9", VirtualKeyCode::Numpad9),
+    ("Up", VirtualKeyCode::Up),
+    ("Down", VirtualKeyCode::Down),
+    ("Left", VirtualKeyCode::Left),
+    ("Right", VirtualKeyCode::Right),
+    ("Home", VirtualKeyCode::Home),
+    ("End", VirtualKeyCode::End),
+    ("PageUp", VirtualKeyCode::PageUp),
+    ("PageDown", VirtualKeyCode::PageDown),
+    ("Insert", VirtualKeyCode::Insert),
+    ("Delete", VirtualKeyCode::Delete),
+    ("Escape", VirtualKeyCode::Escape),
+    ("Enter", VirtualKeyCode::Return),
+    ("Space", VirtualKeyCode::Space),
+    ("Tab", VirtualKeyCode::Tab),
+    ("Backspace", VirtualKeyCode::Back),
+    ("F1", VirtualKeyCode::F1),
+    ("F2", VirtualKeyCode::F2),
+    ("F3", VirtualKeyCode::F3),
+    ("F4", VirtualKeyCode::F4),
+    ("F5", VirtualKeyCode::F5),
+    ("F6", VirtualKeyCode::F6),
+    ("F7", VirtualKeyCode::F7),
+    ("F8", VirtualKeyCode::F8),
+    ("F9", VirtualKeyCode::F9),
+    ("F10", VirtualKeyCode::F10),
+    ("F11", VirtualKeyCode::F11),
+    ("F12", VirtualKeyCode::F12),
+    (".", VirtualKeyCode::Period),
+    (",", VirtualKeyCode::Comma),
+    ("/", VirtualKeyCode::Slash),
+    (";", VirtualKeyCode::Semicolon),
+    ("'", VirtualKeyCode::Apostrophe),
+    ("[", VirtualKeyCode::LBracket),
+    ("]", VirtualKeyCode::RBracket),
+    ("\\", VirtualKeyCode::Backslash),
+    ("-", VirtualKeyCode::Minus),
+    ("=", VirtualKeyCode::Equals),
+    ("`", VirtualKeyCode::Grave),
+];
+
+/// A key, with the modifiers that have to be held down with it.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct Binding {
+    pub key: VirtualKeyCode,
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+impl Binding {
+    /// Reads a binding such as `"K"` or `"Ctrl+Shift+S"`.
+    fn parse(text: &str) -> Result<Binding, String> {
+        let mut binding = Binding {
+            key: VirtualKeyCode::Unlabeled,
+            shift: false,
+            control: false,
+            alt: false,
+        };
+        let mut rest = text;
+        while let Some((modifier, key)) = rest.split_once('+') {
+            if key.is_empty() {
+                break;
+            }
+            if modifier.eq_ignore_ascii_case("Shift") {
+                binding.shift = true;
+            } else if modifier.eq_ignore_ascii_case("Ctrl") {
+                binding.control = true;
+            } else if modifier.eq_ignore_ascii_case("Alt") {
+                binding.alt = true;
+            } else {
+                return Err(format!("unknown modifier in \"{}\"", text));
+            }
+            rest = key;
+        }
+        binding.key = KEYS
+            .iter()
+            .find(|(name, _key)| name.eq_ignore_ascii_case(rest))
+            .map(|(_name, key)| *key)
+            .ok_or_else(|| format!("unknown key \"{}\"", text))?;
+        Ok(binding)
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.control {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        let name = KEYS
+            .iter()
+            .find(|(_name, key)| *key == self.key)
+            .map_or("?", |(name, _key)| name);
+        write!(f, "{}", name)
+    }
+}
+
+/// The keys bound to each action.
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Binding>)>,
+}
+
+impl Keymap {
+    fn defaults() -> Keymap {
+        let bindings = Action::ALL
+            .iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|key| Binding::parse(key).expect("Unable to parse a default binding"))
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+
+    /// The default keymap with the bindings in `json` in place of the defaults for the actions
+    /// it lists. A key it gives to one action is taken away from any other action it was bound
+    /// to by default, but binding one key to two actions in the same context is an error.
+    fn with_overrides(json: &str) -> Result<Keymap, String> {
+        let file: HashMap<Action, Vec<String>> =
+            serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut overrides: Vec<(Action, Vec<Binding>)> = Vec::new();
+        for action in Action::ALL.iter() {
+            if let Some(keys) = file.get(action) {
+                let keys = keys
+                    .iter()
+                    .map(|key| Binding::parse(key))
+                    .collect::<Result<Vec<Binding>, String>>()?;
+                for (other, other_keys) in overrides.iter() {
+                    if other.context() != action.context() {
+                        continue;
+                    }
+                    if let Some(key) = keys.iter().find(|key| other_keys.contains(key)) {
+                        return Err(format!(
+                            "{} is bound to both {:?} and {:?}",
+                            key, other, action
+                        ));
+                    }
+                }
+                overrides.push((*action, keys));
+            }
+        }
+
+        let mut keymap = Keymap::defaults();
+        for (action, keys) in keymap.bindings.iter_mut() {
+            match overrides.iter().find(|(other, _keys)| other == action) {
+                Some((_action, new_keys)) => *keys = new_keys.clone(),
+                None => keys.retain(|key| {
+                    !overrides.iter().any(|(other, other_keys)| {
+                        other.context() == action.context() && other_keys.contains(key)
+                    })
+                }),
+            }
+        }
+        Ok(keymap)
+    }
+
+    fn load() -> Keymap {
+        let path = saveload_system::data_dir().join("keymap.json");
+        let Ok(json) = fs::read_to_string(&path) else {
+            return Keymap::defaults();
+        };
+        Keymap::with_overrides(&json).unwrap_or_else(|e| {
+            eprintln!("Unable to load the keymap in {}: {}", path.display(), e);
+            Keymap::defaults()
+        })
+    }
+
+    /// The action `binding` is bound to in `context`, if any.
+    pub fn action(&self, binding: Binding, context: Context) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(action, keys)| action.context() == context && keys.contains(&binding))
+            .map(|(action, _keys)| *action)
+    }
+
+    /// The keys bound to `action`.
+    pub fn keys(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .iter()
+            .find(|(other, _keys)| *other == action)
+            .map_or(&[], |(_action, keys)| keys)
+    }
+}
+
+static KEYMAP: LazyLock<Keymap> = LazyLock::new(Keymap::load);
+
+pub fn current() -> &'static Keymap {
+    &KEYMAP
+}
+
+/// The action for the key pressed this frame in `context`, if it's bound to one.
+pub fn pressed(ctx: &Rltk, context: Context) -> Option<Action> {
+    let binding = Binding {
+        key: ctx.key?,
+        shift: ctx.shift,
+        control: ctx.control,
+        alt: ctx.alt,
+    };
+    current().action(binding, context)
+}
+
+/// The character typed by the key pressed this frame, for text entry. Only letters, digits,
+/// spaces and a little punctuation can be typed.
+pub fn typed_char(ctx: &Rltk) -> Option<char> {
+    let key = ctx.key?;
+    if key == VirtualKeyCode::Space {
+        return Some(' ');
+    }
+    if ctx.shift && key == VirtualKeyCode::Minus {
+        return Some('_');
+    }
+    let (name, _key) = KEYS.iter().find(|(_name, other)| *other == key)?;
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() && !ctx.shift => Some(c.to_ascii_lowercase()),
+        (Some(c), None) if c.is_ascii_alphanumeric() || "-.,/".contains(c) => Some(c),
+        _ => None,
+    }
+}
+
+/// The keys bound to `action`, as they're written, for showing the player.
+pub fn describe(action: Action) -> String {
+    current()
+        .keys(action)
+        .iter()
+        .map(|key| key.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_read_back_as_written() {
+        for text in ["K", "Shift+.", "Ctrl+Alt+Shift+F5", "Shift++", "Enter"] {
+            match Binding::parse(text) {
+                Ok(binding) => assert_eq!(binding.to_string(), text),
+                Err(_) => assert_eq!(text, "Shift++"),
+            }
+        }
+        assert_eq!(Binding::parse("shift+k"), Binding::parse("Shift+K"));
+        assert!(Binding::parse("Hyper+K").is_err());
+    }
+
+    #[test]
+    fn default_bindings_do_not_conflict() {
+        let keymap = Keymap::defaults();
+        for (action, keys) in keymap.bindings.iter() {
+            assert!(!keys.is_empty(), "{:?}", action);
+            assert!(CATEGORIES.contains(&action.category()), "{:?}", action);
+            for key in keys.iter() {
+                assert_eq!(keymap.action(*key, action.context()), Some(*action));
+            }
+        }
+    }
+
+    #[test]
+    fn overrides_take_keys_from_the_defaults() {
+        let keymap = Keymap::with_overrides(r#"{ "PickUp": ["J", "Comma"] }"#);
+        assert!(keymap.is_err());
+
+        let keymap = Keymap::with_overrides(r#"{ "PickUp": ["J", ","] }"#).unwrap();
+        let j = Binding::parse("J").unwrap();
+        assert_eq!(keymap.action(j, Context::Game), Some(Action::PickUp));
+        assert_eq!(keymap.action(j, Context::Menu), Some(Action::MenuDown));
+        assert_eq!(keymap.keys(Action::MoveSouth).len(), 2);
+        assert!(
+            keymap
+                .keys(Action::PickUp)
+                .iter()
+                .all(|key| *key != Binding::parse("G").unwrap())
+        );
+
+        let conflict = r#"{ "PickUp": ["G"], "Wait": ["Numpad5", "G"] }"#;
+        assert!(Keymap::with_overrides(conflict).is_err());
+        let other_contexts = r#"{ "PickUp": ["G"], "MenuSelect": ["G"] }"#;
+        assert!(Keymap::with_overrides(other_contexts).is_ok());
+    }
+}
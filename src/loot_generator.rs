@@ -0,0 +1,132 @@
+use super::{
+    EquipmentSlot, FactionSlayer, LifeSteal, MagicItem, MagicItemClass, Regeneration, SlowsHunger,
+    ViewRangeBonus, spawner,
+};
+use rltk::{RGB, RandomNumberGenerator};
+use specs::prelude::*;
+
+#[derive(Clone)]
+pub enum Affix {
+    LifeSteal { percent: i32 },
+    FactionSlayer { faction: String, damage: i32 },
+    Regeneration { interval: i32 },
+    Farsight { range: i32 },
+    Sustenance { percent: i32 },
+}
+
+impl Affix {
+    fn prefix(&self) -> Option<String> {
+        match self {
+            Affix::LifeSteal { .. } => Some("Vampiric".to_string()),
+            Affix::Farsight { .. } => Some("Far-seeing".to_string()),
+            Affix::Sustenance { .. } => Some("Sustaining".to_string()),
+            _ => None,
+        }
+    }
+
+    fn suffix(&self) -> Option<String> {
+        match self {
+            Affix::FactionSlayer { faction, .. } => Some(format!("of {} Slaying", faction)),
+            Affix::Regeneration { .. } => Some("of Mending".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// The rarity and affixes rolled for a single piece of equipment.
+pub struct LootRoll {
+    pub class: MagicItemClass,
+    pub affixes: Vec<Affix>,
+}
+
+impl LootRoll {
+    /// Decorates a base item name with (at most) one prefix and one suffix from the affixes.
+    pub fn name(&self, base_name: &str) -> String {
+        let mut name = base_name.to_string();
+        if let Some(prefix) = self.affixes.iter().find_map(|a| a.prefix()) {
+            name = format!("{} {}", prefix, name);
+        }
+        if let Some(suffix) = self.affixes.iter().find_map(|a| a.suffix()) {
+            name = format!("{} {}", name, suffix);
+        }
+        name
+    }
+
+    pub fn apply<'a>(&self, mut builder: EntityBuilder<'a>) -> EntityBuilder<'a> {
+        builder = builder.with(MagicItem { class: self.class });
+        for affix in self.affixes.iter() {
+            builder = match affix {
+                Affix::LifeSteal { percent } => builder.with(LifeSteal { percent: *percent }),
+                Affix::FactionSlayer { faction, damage } => builder.with(FactionSlayer {
+                    faction: faction.clone(),
+                    damage: *damage,
+                }),
+                Affix::Regeneration { interval } => builder.with(Regeneration {
+                    amount: 1,
+                    interval: *interval,
+                    countdown: *interval,
+                }),
+                Affix::Farsight { range } => builder.with(ViewRangeBonus { range: *range }),
+                Affix::Sustenance { percent } => builder.with(SlowsHunger { percent: *percent }),
+            };
+        }
+        builder
+    }
+}
+
+pub fn roll_loot(rng: &mut RandomNumberGenerator, depth: i32, slot: EquipmentSlot) -> LootRoll {
+    let (class, tier) = match rng.roll_dice(1, 100) + depth * 3 {
+        1..=60 => (MagicItemClass::Common, 0),
+        61..=85 => (MagicItemClass::Uncommon, 1),
+        86..=97 => (MagicItemClass::Rare, 2),
+        _ => (MagicItemClass::Legendary, 3),
+    };
+
+    // Each tier adds one distinct affix, drawn from those that suit the slot
+    let mut available: Vec<Affix> = vec![
+        Affix::Regeneration {
+            interval: 12 - tier * 3,
+        },
+        Affix::Farsight { range: tier + 1 },
+        Affix::Sustenance { percent: tier * 25 },
+    ];
+    if slot == EquipmentSlot::Melee {
+        let faction = spawner::FACTIONS
+            [(rng.roll_dice(1, spawner::FACTIONS.len() as i32) - 1) as usize]
+            .to_string();
+        available.push(Affix::LifeSteal {
+            percent: 5 + tier * 10,
+        });
+        available.push(Affix::FactionSlayer {
+            faction,
+            damage: tier * 2,
+        });
+    }
+
+    let mut affixes = Vec::new();
+    for _ in 0..tier {
+        let roll = rng.roll_dice(1, available.len() as i32) - 1;
+        affixes.push(available.remove(roll as usize));
+    }
+
+    LootRoll { class, affixes }
+}
+
+pub fn rarity_colour(class: MagicItemClass) -> RGB {
+    match class {
+        MagicItemClass::Common => RGB::named(rltk::WHITE),
+        MagicItemClass::Uncommon => RGB::named(rltk::GREEN),
+        MagicItemClass::Rare => RGB::named(rltk::SKYBLUE),
+        MagicItemClass::Legendary => RGB::named(rltk::GOLD),
+    }
+}
+
+/// How much more than a plain item of the same kind vendors will pay for each rarity tier.
+pub fn rarity_multiplier(class: MagicItemClass) -> i32 {
+    match class {
+        MagicItemClass::Common => 1,
+        MagicItemClass::Uncommon => 2,
+        MagicItemClass::Rare => 4,
+        MagicItemClass::Legendary => 8,
+    }
+}
@@ -1,8 +1,9 @@
 extern crate serde;
-use rltk::{BTerm, BTermBuilder, GameState, Point, RGB, Rltk};
+use rltk::{GameState, Point, RGB, Rltk};
 use specs::prelude::*;
 use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
 
+mod backend;
 mod components;
 pub use components::*;
 mod map;
@@ -11,6 +12,8 @@ mod player;
 use player::*;
 mod rect;
 pub use rect::Rect;
+mod bot;
+mod cli;
 mod visibility_system;
 use visibility_system::VisibilitySystem;
 mod monster_ai_system;
@@ -24,15 +27,38 @@ use melee_combat_system::MeleeCombatSystem;
 mod gamelog;
 mod gui;
 mod inventory_system;
+mod keymap;
 mod spawner;
+mod theme;
+mod tile_memory_system;
 use crate::hunger_system::HungerSystem;
 use gamelog::GameLog;
-use inventory_system::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
+use inventory_system::{
+    ContainerSystem, ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem,
+};
+use tile_memory_system::TileMemorySystem;
 
+mod encumbrance_system;
+use encumbrance_system::EncumbranceSystem;
 mod hunger_system;
+mod identification_system;
+use identification_system::{IdentificationMap, ItemIdentificationSystem, get_item_display_name};
+mod loot_generator;
+mod morgue;
+mod overview;
 mod particle_system;
 mod random_table;
+mod regeneration_system;
+mod replay;
+mod rle;
+use regeneration_system::RegenerationSystem;
+mod run_info;
+use run_info::RunInfo;
 pub mod saveload_system;
+mod settings;
+mod simulation;
+mod vendor;
+mod wizard;
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
@@ -40,6 +66,8 @@ pub enum RunState {
     MainMenu {
         menu_selection: gui::MainMenuSelection,
     },
+    /// Asks for the character's name before a new game.
+    NameCharacter,
     PreRun,
     PlayerTurn,
     MonsterTurn,
@@ -47,12 +75,32 @@ pub enum RunState {
     ShowTargeting {
         range: i32,
         item: Entity,
+        aim: gui::Aim,
     },
     ShowDropItem,
-    SaveGame,
+    SaveSlots {
+        mode: gui::SlotMenuMode,
+        selection: usize,
+    },
     NextLevel,
     ShowRemoveItem,
     GameOver,
+    ShowVendor {
+        mode: gui::VendorMode,
+    },
+    HighScores,
+    Options {
+        selection: usize,
+    },
+    /// Lists the controls.
+    Help,
+    /// Shows the whole level at once.
+    MapOverview,
+    WizardConsole,
+    /// Asks the player whether to go ahead with `action`.
+    ConfirmAction {
+        action: PlayerAction,
+    },
 }
 
 pub struct State {
@@ -73,18 +121,27 @@ impl State {
         damage.run_now(&self.ecs);
         let mut pickup = ItemCollectionSystem {};
         pickup.run_now(&self.ecs);
+        let mut containers = ContainerSystem {};
+        containers.run_now(&self.ecs);
         let mut potions = ItemUseSystem {};
         potions.run_now(&self.ecs);
+        let mut identification = ItemIdentificationSystem {};
+        identification.run_now(&self.ecs);
         let mut drop_items = ItemDropSystem {};
         drop_items.run_now(&self.ecs);
         let mut item_remove = ItemRemoveSystem {};
         item_remove.run_now(&self.ecs);
+        let mut encumbrance = EncumbranceSystem {};
+        encumbrance.run_now(&self.ecs);
         let mut hunger = HungerSystem {};
         hunger.run_now(&self.ecs);
-        let mut particles = particle_system::ParticleSpawnSystem {};
-        particles.run_now(&self.ecs);
+        let mut regeneration = RegenerationSystem {};
+        regeneration.run_now(&self.ecs);
 
         self.ecs.maintain();
+        // Once everything has moved, so nothing is remembered where it no longer is
+        let mut memory = TileMemorySystem {};
+        memory.run_now(&self.ecs);
     }
 }
 
@@ -139,14 +196,19 @@ impl State {
         let current_depth;
         {
             let mut worldmap_resource = self.ecs.write_resource::<Map>();
+            let mut rng = self.ecs.write_resource::<rltk::RandomNumberGenerator>();
             current_depth = worldmap_resource.depth;
-            *worldmap_resource = Map::new_map_rooms_and_corridors(current_depth + 1);
+            // Every level of a run is the size its first one was
+            let size = (worldmap_resource.width, worldmap_resource.height);
+            *worldmap_resource =
+                Map::new_map_rooms_and_corridors(current_depth + 1, size, &mut rng);
             world_map = worldmap_resource.clone();
         }
 
         for room in world_map.rooms.iter().skip(1) {
             spawner::spawn_room(&mut self.ecs, room, current_depth + 1, &world_map);
         }
+        spawner::spawn_vendor(&mut self.ecs, &world_map);
 
         let (player_x, player_y) = world_map.rooms[0].center();
         let mut player_position = self.ecs.write_resource::<Point>();
@@ -176,51 +238,85 @@ impl State {
         }
     }
 
-    fn game_over_cleanup(&mut self) {
-        // Delete everything
-        let mut to_delete = Vec::new();
-        for e in self.ecs.entities().join() {
-            to_delete.push(e);
-        }
-        for del in to_delete.iter() {
-            self.ecs.delete_entity(*del).expect("Deletion failed");
+    /// Saves to the autosave slot, and writes the replay so far. Nothing is saved while
+    /// watching a replay.
+    fn autosave(&mut self, resume_state: saveload_system::ResumeState) {
+        if replay::is_replaying(&self.ecs) {
+            return;
         }
+        self.save_replay();
+        if let Err(e) =
+            saveload_system::savegame(&mut self.ecs, saveload_system::AUTOSAVE_SLOT, resume_state)
         {
-            let mut log = self.ecs.fetch_mut::<GameLog>();
-            log.entries.clear();
-        }
-        // Build a new map and place the player
-        let worldmap;
-        {
-            let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            *worldmap_resource = Map::new_map_rooms_and_corridors(1);
-            worldmap = worldmap_resource.clone();
+            self.ecs
+                .fetch_mut::<GameLog>()
+                .entries
+                .push(format!("Autosave failed: {}.", e));
         }
+    }
 
-        // Spawn bad guys
-        for room in worldmap.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.ecs, room, 1, &worldmap);
+    /// Writes the run's replay, noting in the log if it couldn't be.
+    fn save_replay(&mut self) {
+        if let Err(e) = replay::save_replay(&self.ecs) {
+            self.ecs
+                .fetch_mut::<GameLog>()
+                .entries
+                .push(format!("Unable to write the replay: {}.", e));
         }
+    }
 
-        // Place the player and update resources
-        let (player_x, player_y) = worldmap.rooms[0].center();
-        let player_entity = spawner::player(&mut self.ecs, player_x, player_y);
-        let mut player_position = self.ecs.write_resource::<Point>();
-        *player_position = Point::new(player_x, player_y);
-        let mut position_components = self.ecs.write_storage::<Position>();
-        let mut player_entity_writer = self.ecs.write_resource::<Entity>();
-        *player_entity_writer = player_entity;
-        let player_pos_comp = position_components.get_mut(player_entity);
-        if let Some(player_pos_comp) = player_pos_comp {
-            player_pos_comp.x = player_x;
-            player_pos_comp.y = player_y;
+    /// Carries the game on from `state`, which mustn't need the player, by one step.
+    fn advance(&mut self, state: RunState) -> RunState {
+        match state {
+            RunState::PreRun => {
+                self.run_systems();
+                RunState::AwaitingInput
+            }
+            RunState::PlayerTurn => {
+                self.ecs.write_resource::<RunInfo>().turns += 1;
+                self.run_systems();
+                encumbrance_system::note_burdened_action(&mut self.ecs);
+                RunState::MonsterTurn
+            }
+            RunState::MonsterTurn => {
+                self.run_systems();
+                if encumbrance_system::burden_grants_extra_turn(&mut self.ecs) {
+                    RunState::MonsterTurn
+                } else {
+                    RunState::AwaitingInput
+                }
+            }
+            RunState::NextLevel => {
+                self.goto_next_level();
+                self.run_systems();
+                RunState::AwaitingInput
+            }
+            state => state,
         }
+    }
 
-        // Mark the player's visibility as dirty
-        let mut viewshed_components = self.ecs.write_storage::<Viewshed>();
-        let vs = viewshed_components.get_mut(player_entity);
-        if let Some(vs) = vs {
-            vs.dirty = true;
+    /// Settles the world at the end of a step, once the game has moved on to `state`.
+    fn end_step(&mut self, state: RunState) {
+        *self.ecs.write_resource::<RunState>() = state;
+        damage_system::delete_the_dead(&mut self.ecs);
+    }
+
+    /// Plays the game on from `state` until it needs the player again, as `tick` would over
+    /// several frames, returning the state it stops in.
+    fn resolve(&mut self, mut state: RunState) -> RunState {
+        loop {
+            self.end_step(state);
+            state = *self.ecs.fetch::<RunState>();
+            if !matches!(
+                state,
+                RunState::PreRun
+                    | RunState::PlayerTurn
+                    | RunState::MonsterTurn
+                    | RunState::NextLevel
+            ) {
+                return state;
+            }
+            state = self.advance(state);
         }
     }
 }
@@ -233,9 +329,10 @@ impl GameState for State {
             new_run_state = *run_state;
         }
 
-        BTerm::set_active_console(ctx, 0);
-        for y in 0..50 {
-            for x in 0..80 {
+        backend::set_layer(ctx, backend::MAP_LAYER);
+        backend::use_tileset(ctx, theme::current().tileset);
+        for y in 0..backend::SCREEN_HEIGHT {
+            for x in 0..backend::SCREEN_WIDTH {
                 ctx.set(
                     x,
                     y,
@@ -246,85 +343,127 @@ impl GameState for State {
             }
         }
 
-        BTerm::set_active_console(ctx, 2);
+        backend::set_layer(ctx, backend::OVERLAY_LAYER);
         ctx.cls();
-        particle_system::cull_dead_particles(&mut self.ecs, ctx);
+        backend::set_layer(ctx, backend::TEXT_LAYER);
+        ctx.cls();
+        particle_system::cull_dead_particles(&mut self.ecs, ctx.frame_time_ms);
 
         match new_run_state {
             RunState::MainMenu { .. } => {}
+            RunState::NameCharacter => {}
+            RunState::SaveSlots {
+                mode: gui::SlotMenuMode::Load,
+                ..
+            } => {}
             RunState::GameOver => {}
+            RunState::HighScores => {}
+            RunState::Options { .. } => {}
+            RunState::Help => {}
+            RunState::MapOverview => {}
             _ => {
-                BTerm::set_active_console(ctx, 0);
+                backend::set_layer(ctx, backend::MAP_LAYER);
                 draw_map(&self.ecs, ctx);
 
                 {
                     let positions = self.ecs.read_storage::<Position>();
                     let renderables = self.ecs.read_storage::<Renderable>();
                     let map = self.ecs.fetch::<Map>();
+                    let theme = theme::current();
 
                     let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-                    data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
+                    data.sort_by_key(|&b| std::cmp::Reverse(b.1.render_order));
                     for (pos, render) in data.iter() {
                         let idx = map.xy_idx(pos.x, pos.y);
                         if map.visible_tiles[idx] {
-                            let bg = get_tile_bg(&map, idx);
-                            ctx.set(pos.x, pos.y, render.fg, bg, render.glyph);
+                            let look = theme.look(&render.look);
+                            let bg = get_tile_bg(&map, &theme, idx);
+                            let glyph = backend::glyph(look.glyph, theme.tileset);
+                            ctx.set(pos.x, pos.y, look.fg, bg, glyph);
+                        }
+                    }
+
+                    let particles = self.ecs.fetch::<particle_system::ParticleBuilder>();
+                    for particle in particles.particles() {
+                        let idx = map.xy_idx(particle.x, particle.y);
+                        if map.visible_tiles[idx] {
+                            let look = theme.look(particle.look);
+                            let bg = get_tile_bg(&map, &theme, idx);
+                            let glyph = backend::glyph(look.glyph, theme.tileset);
+                            ctx.set(particle.x, particle.y, look.fg, bg, glyph);
                         }
                     }
                 }
 
-                BTerm::set_active_console(ctx, 2);
+                overview::draw_minimap(&self.ecs, ctx);
+                backend::set_layer(ctx, backend::TEXT_LAYER);
                 gui::draw_ui(&self.ecs, ctx);
             }
         }
 
         match new_run_state {
-            RunState::PreRun => {
-                self.run_systems();
-                self.ecs.maintain();
-                BTerm::set_active_console(ctx, 0);
-                new_run_state = RunState::AwaitingInput;
+            RunState::PreRun | RunState::PlayerTurn => {
+                new_run_state = self.advance(new_run_state);
             }
             RunState::AwaitingInput => {
-                new_run_state = player_input(self, ctx);
-            }
-            RunState::PlayerTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                new_run_state = RunState::MonsterTurn;
+                if replay::is_replaying(&self.ecs) {
+                    new_run_state = replay::playback_input(self, ctx);
+                } else {
+                    new_run_state = player_input(self, ctx);
+                }
             }
             RunState::MonsterTurn => {
-                self.run_systems();
-                self.ecs.maintain();
-                new_run_state = RunState::AwaitingInput;
+                new_run_state = self.advance(new_run_state);
+                let turns = self.ecs.fetch::<RunInfo>().turns;
+                if new_run_state == RunState::AwaitingInput
+                    && turns % saveload_system::AUTOSAVE_INTERVAL == 0
+                {
+                    self.autosave(saveload_system::ResumeState::AwaitingInput);
+                }
             }
             RunState::ShowInventory => {
-                BTerm::set_active_console(ctx, 2);
+                backend::set_layer(ctx, backend::TEXT_LAYER);
                 let result = gui::show_inventory(self, ctx);
                 match result.0 {
                     gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
                         let item_entity = result.1.unwrap();
-                        let is_ranged = self.ecs.read_storage::<Ranged>();
-                        let is_item_ranged = is_ranged.get(item_entity);
-                        if let Some(is_item_ranged) = is_item_ranged {
+                        let ranged = self
+                            .ecs
+                            .read_storage::<Ranged>()
+                            .get(item_entity)
+                            .map(|ranged| ranged.range);
+                        let is_drained = self
+                            .ecs
+                            .read_storage::<Charges>()
+                            .get(item_entity)
+                            .is_some_and(|charge| charge.current < 1);
+                        if is_drained {
+                            let item_name = get_item_display_name(
+                                &self.ecs.read_storage::<Name>(),
+                                &self.ecs.read_storage::<ObfuscatedName>(),
+                                item_entity,
+                            );
+                            let mut gamelog = self.ecs.fetch_mut::<GameLog>();
+                            gamelog
+                                .entries
+                                .push(format!("The {} is out of charge.", item_name));
+                            new_run_state = RunState::AwaitingInput;
+                        } else if let Some(range) = ranged {
                             new_run_state = RunState::ShowTargeting {
-                                range: is_item_ranged.range,
+                                range,
                                 item: item_entity,
+                                aim: gui::Aim::new(&self.ecs, ctx),
                             };
                         } else {
-                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                            intent
-                                .insert(
-                                    *self.ecs.fetch::<Entity>(),
-                                    WantsToUseItem {
-                                        item: item_entity,
-                                        target: None,
-                                    },
-                                )
-                                .expect("Unable to insert intent");
-                            new_run_state = RunState::PlayerTurn;
+                            new_run_state = perform_action(
+                                &mut self.ecs,
+                                PlayerAction::UseItem {
+                                    item: item_entity,
+                                    target: None,
+                                },
+                            );
                         }
                     }
                 }
@@ -335,40 +474,56 @@ impl GameState for State {
                     gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToDropItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToDropItem { item: item_entity },
-                            )
-                            .expect("Unable to insert intent");
-                        new_run_state = RunState::PlayerTurn;
+                        let item = result.1.unwrap();
+                        new_run_state =
+                            perform_action(&mut self.ecs, PlayerAction::DropItem { item });
                     }
                 }
             }
-            RunState::ShowTargeting { range, item } => {
-                let result = gui::ranged_target(self, ctx, range);
-                match result.0 {
-                    gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
-                    gui::ItemMenuResult::Selected => {
-                        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToUseItem {
-                                    item,
-                                    target: result.1,
-                                },
-                            )
-                            .expect("Unable to insert intent");
-                        new_run_state = RunState::PlayerTurn;
+            RunState::Help => {
+                if gui::help_screen(ctx) {
+                    new_run_state = RunState::AwaitingInput;
+                }
+            }
+            RunState::MapOverview => {
+                if overview::overview(&self.ecs, ctx) {
+                    new_run_state = RunState::AwaitingInput;
+                }
+            }
+            RunState::WizardConsole => {
+                if wizard::console(self, ctx) {
+                    new_run_state = RunState::AwaitingInput;
+                }
+            }
+            RunState::ConfirmAction { action } => {
+                let question = confirmation(&self.ecs, action).unwrap_or_default();
+                match gui::confirm(ctx, &question) {
+                    gui::ConfirmResult::NoResponse => {}
+                    gui::ConfirmResult::Yes => {
+                        new_run_state = perform_action(&mut self.ecs, action);
+                    }
+                    gui::ConfirmResult::No => new_run_state = RunState::AwaitingInput,
+                }
+            }
+            RunState::ShowTargeting { range, item, aim } => {
+                match gui::ranged_target(self, ctx, range, aim) {
+                    gui::TargetResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::TargetResult::NoResponse { aim } => {
+                        new_run_state = RunState::ShowTargeting { range, item, aim };
+                    }
+                    gui::TargetResult::Selected { target } => {
+                        new_run_state = perform_action(
+                            &mut self.ecs,
+                            PlayerAction::UseItem {
+                                item,
+                                target: Some(target),
+                            },
+                        );
                     }
                 }
             }
             RunState::MainMenu { .. } => {
-                BTerm::set_active_console(ctx, 2);
+                backend::set_layer(ctx, backend::TEXT_LAYER);
                 let result = gui::main_menu(self, ctx);
                 match result {
                     gui::MainMenuResult::NoSelection { selected } => {
@@ -377,11 +532,24 @@ impl GameState for State {
                         }
                     }
                     gui::MainMenuResult::Selected { selected } => match selected {
-                        gui::MainMenuSelection::NewGame => new_run_state = RunState::PreRun,
+                        gui::MainMenuSelection::NewGame => {
+                            self.ecs.write_resource::<RunInfo>().character.clear();
+                            new_run_state = RunState::NameCharacter;
+                        }
                         gui::MainMenuSelection::LoadGame => {
-                            saveload_system::load_game(&mut self.ecs);
-                            new_run_state = RunState::AwaitingInput;
-                            saveload_system::delete_save();
+                            new_run_state = RunState::SaveSlots {
+                                mode: gui::SlotMenuMode::Load,
+                                selection: saveload_system::first_used_slot().unwrap_or(0),
+                            };
+                        }
+                        gui::MainMenuSelection::HighScores => {
+                            self.ecs.insert(morgue::HighScoreTable {
+                                scores: morgue::high_scores(),
+                            });
+                            new_run_state = RunState::HighScores;
+                        }
+                        gui::MainMenuSelection::Options => {
+                            new_run_state = RunState::Options { selection: 0 }
                         }
                         gui::MainMenuSelection::Quit => {
                             std::process::exit(0);
@@ -389,15 +557,102 @@ impl GameState for State {
                     },
                 }
             }
-            RunState::SaveGame => {
-                saveload_system::savegame(&mut self.ecs);
-                new_run_state = RunState::MainMenu {
-                    menu_selection: gui::MainMenuSelection::LoadGame,
-                };
+            RunState::NameCharacter => {
+                backend::set_layer(ctx, backend::TEXT_LAYER);
+                match gui::name_character(self, ctx) {
+                    gui::NameEntryResult::NoResponse => {}
+                    gui::NameEntryResult::Cancel => {
+                        new_run_state = RunState::MainMenu {
+                            menu_selection: gui::MainMenuSelection::NewGame,
+                        };
+                    }
+                    gui::NameEntryResult::Done => new_run_state = RunState::PreRun,
+                }
+            }
+            RunState::SaveSlots { mode, selection } => {
+                backend::set_layer(ctx, backend::TEXT_LAYER);
+                let result = gui::save_slot_menu(ctx, mode, selection);
+                match result {
+                    gui::SlotMenuResult::NoSelection { selected } => {
+                        new_run_state = RunState::SaveSlots {
+                            mode,
+                            selection: selected,
+                        };
+                    }
+                    gui::SlotMenuResult::Cancel => {
+                        new_run_state = match mode {
+                            gui::SlotMenuMode::Save => RunState::AwaitingInput,
+                            gui::SlotMenuMode::Load => RunState::MainMenu {
+                                menu_selection: gui::MainMenuSelection::LoadGame,
+                            },
+                        };
+                    }
+                    gui::SlotMenuResult::Delete { selected } => {
+                        if let Err(e) = saveload_system::delete_save(selected) {
+                            self.ecs
+                                .fetch_mut::<GameLog>()
+                                .entries
+                                .push(format!("Unable to delete the save: {}.", e));
+                        }
+                        if mode == gui::SlotMenuMode::Load && !saveload_system::does_save_exist() {
+                            new_run_state = RunState::MainMenu {
+                                menu_selection: gui::MainMenuSelection::NewGame,
+                            };
+                        }
+                    }
+                    gui::SlotMenuResult::Selected { selected } => match mode {
+                        gui::SlotMenuMode::Save => {
+                            match saveload_system::savegame(
+                                &mut self.ecs,
+                                selected,
+                                saveload_system::ResumeState::AwaitingInput,
+                            ) {
+                                Ok(()) => {
+                                    // The run now lives in the chosen slot
+                                    let _ = saveload_system::delete_save(
+                                        saveload_system::AUTOSAVE_SLOT,
+                                    );
+                                    self.save_replay();
+                                    self.ecs = new_world(RunInfo::random_seed());
+                                    new_run_state = RunState::MainMenu {
+                                        menu_selection: gui::MainMenuSelection::LoadGame,
+                                    };
+                                }
+                                Err(e) => {
+                                    self.ecs
+                                        .fetch_mut::<GameLog>()
+                                        .entries
+                                        .push(format!("Unable to save the game: {}.", e));
+                                    new_run_state = RunState::AwaitingInput;
+                                }
+                            }
+                        }
+                        gui::SlotMenuMode::Load => match saveload_system::load_game(selected) {
+                            Ok((world, resume_state)) => {
+                                self.ecs = world;
+                                replay::record_reload(&mut self.ecs);
+                                new_run_state = resume_state;
+                                if let Err(e) = saveload_system::delete_save(selected) {
+                                    self.ecs
+                                        .fetch_mut::<GameLog>()
+                                        .entries
+                                        .push(format!("Unable to remove the loaded save: {}.", e));
+                                }
+                            }
+                            Err(e) => {
+                                self.ecs.fetch_mut::<gui::MainMenuNotice>().message =
+                                    Some(format!("Unable to load the save: {}.", e));
+                                new_run_state = RunState::MainMenu {
+                                    menu_selection: gui::MainMenuSelection::LoadGame,
+                                };
+                            }
+                        },
+                    },
+                }
             }
             RunState::NextLevel => {
-                self.goto_next_level();
-                new_run_state = RunState::PreRun;
+                new_run_state = self.advance(new_run_state);
+                self.autosave(saveload_system::ResumeState::AwaitingInput);
             }
             RunState::ShowRemoveItem => {
                 let result = gui::remove_item_menu(self, ctx);
@@ -405,24 +660,66 @@ impl GameState for State {
                     gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
                     gui::ItemMenuResult::NoResponse => {}
                     gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
-                        intent
-                            .insert(
-                                *self.ecs.fetch::<Entity>(),
-                                WantsToRemoveItem { item: item_entity },
-                            )
-                            .expect("Unable to insert intent");
-                        new_run_state = RunState::PlayerTurn;
+                        let item = result.1.unwrap();
+                        new_run_state =
+                            perform_action(&mut self.ecs, PlayerAction::RemoveItem { item });
+                    }
+                }
+            }
+            RunState::ShowVendor { mode } => {
+                let result = gui::show_vendor(self, ctx, mode);
+                match result.0 {
+                    gui::VendorResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::VendorResult::NoResponse => {}
+                    gui::VendorResult::Sell => {
+                        new_run_state = perform_action(
+                            &mut self.ecs,
+                            PlayerAction::Sell {
+                                item: result.1.unwrap(),
+                            },
+                        );
                     }
+                    gui::VendorResult::BuyItem => {
+                        new_run_state = perform_action(
+                            &mut self.ecs,
+                            PlayerAction::Buy {
+                                stock: result.2.unwrap(),
+                            },
+                        );
+                    }
+                    gui::VendorResult::ToggleMode => {
+                        new_run_state = RunState::ShowVendor {
+                            mode: match mode {
+                                gui::VendorMode::Buy => gui::VendorMode::Sell,
+                                gui::VendorMode::Sell => gui::VendorMode::Buy,
+                            },
+                        };
+                    }
+                }
+            }
+            RunState::HighScores => {
+                if gui::high_scores(&self.ecs, ctx) {
+                    new_run_state = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::HighScores,
+                    };
                 }
             }
+            RunState::Options { selection } => match gui::options_menu(ctx, selection) {
+                gui::OptionsMenuResult::NoResponse { selection } => {
+                    new_run_state = RunState::Options { selection };
+                }
+                gui::OptionsMenuResult::Back => {
+                    new_run_state = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::Options,
+                    };
+                }
+            },
             RunState::GameOver => {
-                let result = gui::game_over(ctx);
+                let result = gui::game_over(&self.ecs, ctx);
                 match result {
                     gui::GameOverResult::NoSelection => {}
                     gui::GameOverResult::QuitToMenu => {
-                        self.game_over_cleanup();
+                        self.ecs = new_world(RunInfo::random_seed());
                         new_run_state = RunState::MainMenu {
                             menu_selection: gui::MainMenuSelection::NewGame,
                         };
@@ -430,87 +727,204 @@ impl GameState for State {
                 }
             }
         }
+        self.end_step(new_run_state);
+
+        // Permadeath: once the player dies, the autosave mustn't bring them back
+        if new_run_state != RunState::GameOver
+            && *self.ecs.fetch::<RunState>() == RunState::GameOver
+            && !replay::is_replaying(&self.ecs)
         {
-            let mut run_writer = self.ecs.write_resource::<RunState>();
-            *run_writer = new_run_state;
+            let _ = saveload_system::delete_save(saveload_system::AUTOSAVE_SLOT);
+            self.save_replay();
+            morgue::record_death(&mut self.ecs);
         }
-
-        damage_system::delete_the_dead(&mut self.ecs);
     }
 }
 
-fn main() -> rltk::BError {
-    let mut context = BTermBuilder::new()
-        .with_title("RogueSpace")
-        .with_fps_cap(30.0)
-        .with_dimensions(80, 50)
-        .with_tile_dimensions(16, 16)
-        .with_resource_path("resources/")
-        .with_font("monochrome-transparent_packed.png", 16, 16)
-        .with_font("terminal8x8.jpg", 8, 8)
-        .with_simple_console(80, 50, "monochrome-transparent_packed.png")
-        .with_simple_console_no_bg(80, 50, "monochrome-transparent_packed.png")
-        .with_simple_console_no_bg(80, 50, "terminal8x8.jpg")
-        .build()?;
-    context.with_post_scanlines(true);
-
-    let mut gs = State { ecs: World::new() };
-
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToUseItem>();
-    gs.ecs.register::<WantsToDropItem>();
-    gs.ecs.register::<Consumable>();
-    gs.ecs.register::<ProvidesHealing>();
-    gs.ecs.register::<Ranged>();
-    gs.ecs.register::<InflictsDamage>();
-    gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<Confusion>();
-    gs.ecs.register::<SimpleMarker<SerializeMe>>();
-    gs.ecs.register::<SerializationHelper>();
-    gs.ecs.register::<Equippable>();
-    gs.ecs.register::<Equipped>();
-    gs.ecs.register::<MeleePowerBonus>();
-    gs.ecs.register::<DefenseBonus>();
-    gs.ecs.register::<WantsToRemoveItem>();
-    gs.ecs.register::<ParticleLifetime>();
-    gs.ecs.register::<HungerClock>();
-    gs.ecs.register::<ProvidesFood>();
-
-    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
-
-    let map: Map = Map::new_map_rooms_and_corridors(1);
+/// Builds a world with every component registered but nothing in it, ready for a save to be
+/// loaded into.
+pub fn empty_world() -> World {
+    let mut ecs = World::new();
+
+    ecs.register::<Position>();
+    ecs.register::<Renderable>();
+    ecs.register::<Player>();
+    ecs.register::<Viewshed>();
+    ecs.register::<Monster>();
+    ecs.register::<Name>();
+    ecs.register::<BlocksTile>();
+    ecs.register::<CombatStats>();
+    ecs.register::<SufferDamage>();
+    ecs.register::<WantsToMelee>();
+    ecs.register::<Item>();
+    ecs.register::<InBackpack>();
+    ecs.register::<WantsToPickupItem>();
+    ecs.register::<WantsToUseItem>();
+    ecs.register::<WantsToDropItem>();
+    ecs.register::<Consumable>();
+    ecs.register::<ProvidesHealing>();
+    ecs.register::<Ranged>();
+    ecs.register::<InflictsDamage>();
+    ecs.register::<AreaOfEffect>();
+    ecs.register::<Confusion>();
+    ecs.register::<SimpleMarker<SerializeMe>>();
+    ecs.register::<SerializationHelper>();
+    ecs.register::<Equippable>();
+    ecs.register::<Equipped>();
+    ecs.register::<MeleePowerBonus>();
+    ecs.register::<DefenseBonus>();
+    ecs.register::<WantsToRemoveItem>();
+    ecs.register::<HungerClock>();
+    ecs.register::<ProvidesFood>();
+    ecs.register::<ObfuscatedName>();
+    ecs.register::<IdentifiedItem>();
+    ecs.register::<ProvidesIdentification>();
+    ecs.register::<CursedItem>();
+    ecs.register::<ProvidesRemoveCurse>();
+    ecs.register::<MagicItem>();
+    ecs.register::<Faction>();
+    ecs.register::<LifeSteal>();
+    ecs.register::<FactionSlayer>();
+    ecs.register::<Regeneration>();
+    ecs.register::<ViewRangeBonus>();
+    ecs.register::<SlowsHunger>();
+    ecs.register::<Charges>();
+    ecs.register::<ProvidesRecharge>();
+    ecs.register::<Burdened>();
+    ecs.register::<Wallet>();
+    ecs.register::<Currency>();
+    ecs.register::<Vendor>();
+    ecs.register::<LootContainer>();
+    ecs.register::<WantsToOpenContainer>();
+
+    ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    ecs.insert(RunState::PreRun);
+    ecs.insert(particle_system::ParticleBuilder::new());
+    ecs.insert(gui::MainMenuNotice::default());
+    ecs.insert(wizard::WizardPowers::default());
+
+    ecs
+}
+
+/// Builds a complete world for a new run from `seed`: every component registered, every
+/// resource inserted and the first level generated. It needs no window, so tests and tools can
+/// build a game too.
+pub fn new_world(seed: u64) -> World {
+    new_world_of_size(seed, map::level_size())
+}
+
+/// Builds the world for a new run whose levels are `size` tiles across and down.
+pub fn new_world_of_size(seed: u64, size: (i32, i32)) -> World {
+    let mut ecs = empty_world();
+
+    let (run_info, mut rng) = RunInfo::seeded_run(seed);
+    let map: Map = Map::new_map_rooms_and_corridors(1, size, &mut rng);
     let (player_x, player_y) = map.rooms[0].center();
 
-    let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
+    let player_entity = spawner::player(&mut ecs, player_x, player_y);
 
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
+    ecs.insert(IdentificationMap::new(&mut rng));
+    ecs.insert(rng);
+    ecs.insert(replay::Recording::new(seed, size));
+    ecs.insert(run_info);
+    ecs.insert(map.clone());
     for room in map.rooms.iter().skip(1) {
-        spawner::spawn_room(&mut gs.ecs, room, 1, &map);
+        spawner::spawn_room(&mut ecs, room, 1, &map);
+    }
+
+    ecs.insert(Point::new(player_x, player_y));
+    ecs.insert(GameLog {
+        entries: vec!["Welcome to RogueSpace".to_string()],
+    });
+    ecs.insert(player_entity);
+
+    ecs
+}
+
+fn main() -> rltk::BError {
+    let args: Vec<String> = std::env::args().collect();
+    let options = match cli::Options::from_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::usage());
+            std::process::exit(2);
+        }
+    };
+    if let Some(dir) = &options.save_dir {
+        saveload_system::set_data_dir(dir.clone());
+    }
+    if let Some((width, height)) = options.level_size {
+        map::set_level_size(width, height);
+    }
+    if options.wizard {
+        wizard::enable();
     }
+    let seed = options.seed.unwrap_or_else(RunInfo::random_seed);
 
-    gs.ecs.insert(map);
+    match &options.mode {
+        cli::Mode::Play => {}
+        cli::Mode::Help => {
+            println!("{}", cli::usage());
+            return Ok(());
+        }
+        cli::Mode::Simulate(simulation) => {
+            match simulation::run(simulation) {
+                Ok(summaries) => {
+                    let games = summaries.len().max(1) as f32;
+                    let depth: i32 = summaries.iter().map(|s| s.depth).sum();
+                    let turns: i32 = summaries.iter().map(|s| s.turns).sum();
+                    println!(
+                        "Simulated {} games: average depth {:.2}, average turns {:.0}. Results written to {}.",
+                        summaries.len(),
+                        depth as f32 / games,
+                        turns as f32 / games,
+                        simulation.output.display()
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Unable to write the simulation results: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        cli::Mode::DumpMap { depth } => {
+            println!("Seed {}, depth {}:", seed, depth);
+            println!("{}", cli::dump_map(seed, *depth));
+            return Ok(());
+        }
+        cli::Mode::Headless => {
+            match cli::play_headless(&options, seed) {
+                Ok(summary) => {
+                    println!("{}", simulation::GameSummary::CSV_HEADER);
+                    println!("{}", summary.csv_row());
+                }
+                Err(e) => {
+                    eprintln!("Unable to read the replay: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    let context = backend::build_context(&settings::current())?;
+
+    let mut gs = State {
+        ecs: new_world(seed),
+    };
     gs.ecs.insert(RunState::MainMenu {
         menu_selection: gui::MainMenuSelection::NewGame,
     });
-    gs.ecs.insert(Point::new(player_x, player_y));
-    gs.ecs.insert(GameLog {
-        entries: vec!["Welcome to RogueSpace".to_string()],
-    });
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
-    gs.ecs.insert(player_entity);
-    gs.ecs.insert(particle_system::ParticleBuilder::new());
+    if let Some(path) = options.replay {
+        match replay::read_replay(&path) {
+            Ok(file) => gs.ecs = replay::start_playback(file),
+            Err(e) => {
+                eprintln!("Unable to read the replay {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     rltk::main_loop(context, gs)
 }
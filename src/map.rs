@@ -1,13 +1,35 @@
-use super::Rect;
+use super::{
+    Rect, backend,
+    theme::{self, Theme},
+};
 use rltk::{Algorithm2D, BaseMap, Point, RGB, RandomNumberGenerator, Rltk};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use std::cmp::{max, min};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
 
 pub const MAP_WIDTH: usize = 80;
 pub const MAP_HEIGHT: usize = 43;
 pub const MAP_COUNT: usize = MAP_WIDTH * MAP_HEIGHT;
+/// The smallest level that still has room for a few rooms and the corridors between them.
+pub const MIN_MAP_WIDTH: i32 = 40;
+pub const MIN_MAP_HEIGHT: i32 = 20;
+
+/// The width and height new runs are generated at, if set from the command line.
+static LEVEL_SIZE: OnceLock<(i32, i32)> = OnceLock::new();
+
+/// Makes new runs generate their levels at `width` by `height`, which must fit on the screen.
+pub fn set_level_size(width: i32, height: i32) {
+    let _ = LEVEL_SIZE.set((width, height));
+}
+
+/// The width and height new runs are generated at; a run keeps the size it started with.
+pub fn level_size() -> (i32, i32) {
+    *LEVEL_SIZE
+        .get()
+        .unwrap_or(&(MAP_WIDTH as i32, MAP_HEIGHT as i32))
+}
 
 #[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
@@ -22,11 +44,17 @@ pub struct Map {
     pub rooms: Vec<Rect>,
     pub width: i32,
     pub height: i32,
+    #[serde(with = "crate::rle")]
     pub revealed_tiles: Vec<bool>,
+    #[serde(with = "crate::rle")]
     pub visible_tiles: Vec<bool>,
+    #[serde(with = "crate::rle")]
     pub blocked: Vec<bool>,
     pub depth: i32,
     pub bloodstains: HashSet<usize>,
+    /// The look of whatever stays put, such as an item, on each tile the player has seen it on,
+    /// as it was when the tile was last in view.
+    pub remembered: BTreeMap<usize, String>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
@@ -71,26 +99,30 @@ impl Map {
 
     /// Makes a new map using the algorithm from http://rogueliketutorials.com/tutorials/tcod/part-3/
     /// This gives a handful of random rooms and corridors joining them together.
-    pub fn new_map_rooms_and_corridors(new_depth: i32) -> Map {
+    pub fn new_map_rooms_and_corridors(
+        new_depth: i32,
+        (width, height): (i32, i32),
+        rng: &mut RandomNumberGenerator,
+    ) -> Map {
+        let count = (width * height) as usize;
         let mut map = Map {
-            tiles: vec![TileType::Wall; MAP_COUNT],
+            tiles: vec![TileType::Wall; count],
             rooms: Vec::new(),
-            width: MAP_WIDTH as i32,
-            height: MAP_HEIGHT as i32,
-            revealed_tiles: vec![false; MAP_COUNT],
-            visible_tiles: vec![false; MAP_COUNT],
-            blocked: vec![false; MAP_COUNT],
-            tile_content: vec![Vec::new(); MAP_COUNT],
+            width,
+            height,
+            revealed_tiles: vec![false; count],
+            visible_tiles: vec![false; count],
+            blocked: vec![false; count],
+            tile_content: vec![Vec::new(); count],
             depth: new_depth,
             bloodstains: HashSet::new(),
+            remembered: BTreeMap::new(),
         };
 
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
 
-        let mut rng = RandomNumberGenerator::new();
-
         for _i in 0..MAX_ROOMS {
             let w = rng.range(MIN_SIZE, MAX_SIZE);
             let h = rng.range(MIN_SIZE, MAX_SIZE);
@@ -208,46 +240,51 @@ impl BaseMap for Map {
 
 pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
+    let theme = theme::current();
 
     let mut x = 0;
     let mut y = 0;
     for (idx, tile) in map.tiles.iter().enumerate() {
         if map.revealed_tiles[idx] {
-            let glyph;
-            let mut fg;
-            let mut bg = RGB::from_f32(0., 0., 0.);
-            match tile {
-                TileType::Floor => {
-                    glyph = 2;
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
+            let look = theme.look(match tile {
+                TileType::Floor if theme.cues && map.bloodstains.contains(&idx) => {
+                    "floor.bloodstain"
                 }
-                TileType::Wall => {
-                    glyph = wall_glyph(&map, x, y);
-                    fg = RGB::from_f32(0., 1.0, 0.);
-                }
-                TileType::DownStairs => {
-                    glyph = 297;
-                    fg = RGB::from_f32(0.0, 1.0, 1.0);
-                }
-            }
-            if map.bloodstains.contains(&idx) {
-                bg = RGB::from_f32(0.75, 0., 0.);
-            }
+                TileType::Floor => "floor",
+                TileType::Wall => wall_look(&map, x, y),
+                TileType::DownStairs => "stairs.down",
+            });
+            let mut fg = look.fg;
+            let mut bg = get_tile_bg(&map, &theme, idx);
             if !map.visible_tiles[idx] {
                 fg = fg.to_greyscale();
                 bg = bg.to_greyscale();
             }
-            ctx.set(x, y, fg, bg, glyph);
+            ctx.set(x, y, fg, bg, backend::glyph(look.glyph, theme.tileset));
+
+            // Entities are only drawn in view, so out of sight this is all there is
+            if let Some(remembered) = map.remembered.get(&idx)
+                && !map.visible_tiles[idx]
+            {
+                let look = theme.look(remembered);
+                ctx.set(
+                    x,
+                    y,
+                    look.fg.to_greyscale(),
+                    bg,
+                    backend::glyph(look.glyph, theme.tileset),
+                );
+            }
         }
         x += 1;
-        if x > 79 {
+        if x >= map.width {
             x = 0;
             y += 1;
         }
     }
 }
 
-fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
+fn wall_look(map: &Map, x: i32, y: i32) -> &'static str {
     // if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 as i32 {
     //     return 0;
     // }
@@ -267,23 +304,23 @@ fn wall_glyph(map: &Map, x: i32, y: i32) -> rltk::FontCharType {
     }
 
     match mask {
-        0 => 953,   // Pillar because we can't see neighbors DONE
-        1 => 904,   // Wall only to the north DONE
-        2 => 806,   // Wall only to the south DONE
-        3 => 855,   // Wall to the north and south DONE
-        4 => 952,   // Wall only to the west DONE
-        5 => 957,   // Wall to the north and west DONE
-        6 => 810,   // Wall to the south and west DONE
-        7 => 1006,  // Wall to the north, south and west DONE
-        8 => 950,   // Wall only to the east DONE
-        9 => 954,   // Wall to the north and east DONE
-        10 => 807,  // Wall to the south and east
-        11 => 1003, // Wall to the north, south and east
-        12 => 951,  // Wall to the east and west
-        13 => 958,  // Wall to the east, west, and south
-        14 => 811,  // Wall to the east, west, and north
-        15 => 860,  // ╬ Wall on all sides
-        _ => 0,     // We missed one?
+        0 => "wall.pillar", // Pillar because we can't see neighbors
+        1 => "wall.n",      // Wall only to the north
+        2 => "wall.s",      // Wall only to the south
+        3 => "wall.ns",     // Wall to the north and south
+        4 => "wall.w",      // Wall only to the west
+        5 => "wall.nw",     // Wall to the north and west
+        6 => "wall.sw",     // Wall to the south and west
+        7 => "wall.nsw",    // Wall to the north, south and west
+        8 => "wall.e",      // Wall only to the east
+        9 => "wall.ne",     // Wall to the north and east
+        10 => "wall.se",    // Wall to the south and east
+        11 => "wall.nse",   // Wall to the north, south and east
+        12 => "wall.ew",    // Wall to the east and west
+        13 => "wall.new",   // Wall to the east, west, and north
+        14 => "wall.sew",   // Wall to the east, west, and south
+        15 => "wall.nsew",  // ╬ Wall on all sides
+        _ => "wall",        // We missed one?
     }
 }
 
@@ -295,10 +332,10 @@ fn is_revealed_and_wall(map: &Map, x: i32, y: i32) -> bool {
     map.tiles[idx] == TileType::Wall && map.revealed_tiles[idx]
 }
 
-pub fn get_tile_bg(map: &Map, idx: usize) -> RGB {
+pub fn get_tile_bg(map: &Map, theme: &Theme, idx: usize) -> RGB {
     let mut bg = RGB::from_f32(0., 0., 0.);
     if map.bloodstains.contains(&idx) {
-        bg = RGB::from_f32(0.75, 0., 0.);
+        bg = theme.look("bloodstain").fg;
     }
     bg
 }
@@ -1,18 +1,20 @@
 use super::{
-    CombatStats, DefenseBonus, Equipped, HungerClock, HungerState, MeleePowerBonus, Name, Position,
-    SufferDamage, WantsToMelee, gamelog::GameLog, particle_system::ParticleBuilder,
+    CombatStats, DefenseBonus, Equipped, Faction, FactionSlayer, HungerClock, HungerState,
+    LifeSteal, MeleePowerBonus, Name, Position, SufferDamage, WantsToMelee, gamelog::GameLog,
+    particle_system::ParticleBuilder, run_info::RunInfo,
 };
 use specs::prelude::*;
 
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
         Entities<'a>,
         WriteExpect<'a, GameLog>,
         WriteStorage<'a, WantsToMelee>,
         ReadStorage<'a, Name>,
-        ReadStorage<'a, CombatStats>,
+        WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SufferDamage>,
         ReadStorage<'a, MeleePowerBonus>,
         ReadStorage<'a, DefenseBonus>,
@@ -20,6 +22,11 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, HungerClock>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, FactionSlayer>,
+        ReadStorage<'a, LifeSteal>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunInfo>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -28,7 +35,7 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut log,
             mut wants_melee,
             names,
-            combat_stats,
+            mut combat_stats,
             mut inflict_damage,
             melee_power_bonuses,
             defense_bonuses,
@@ -36,8 +43,15 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut particle_builder,
             positions,
             hunger_clocks,
+            factions,
+            faction_slayers,
+            life_steals,
+            player_entity,
+            mut run_info,
         ) = data;
 
+        let mut heals: Vec<(Entity, i32)> = Vec::new();
+
         for (entity, wants_melee, name, stats) in
             (&entities, &wants_melee, &names, &combat_stats).join()
         {
@@ -71,21 +85,37 @@ impl<'a> System<'a> for MeleeCombatSystem {
                     }
                     let pos = positions.get(wants_melee.target);
                     if let Some(pos) = pos {
-                        particle_builder.request(
-                            pos.x,
-                            pos.y,
-                            rltk::RGB::named(rltk::WHITE),
-                            rltk::RGB::named(rltk::BLACK),
-                            575,
-                            200.0,
-                        );
+                        particle_builder.request(pos.x, pos.y, "particle.hit", 200.0);
                     }
-                    let damage = i32::max(
+                    let mut damage = i32::max(
                         0,
                         (stats.power + offensive_bonus) - (target_stats.defense + defensive_bonus),
                     );
+
+                    let mut life_steal_percent = 0;
+                    if let Some(target_faction) = factions.get(wants_melee.target) {
+                        for (_item_entity, slayer, equipped_by) in
+                            (&entities, &faction_slayers, &equipped).join()
+                        {
+                            if equipped_by.owner == entity && slayer.faction == target_faction.name
+                            {
+                                damage += slayer.damage;
+                            }
+                        }
+                    }
+                    for (_item_entity, life_steal, equipped_by) in
+                        (&entities, &life_steals, &equipped).join()
+                    {
+                        if equipped_by.owner == entity {
+                            life_steal_percent += life_steal.percent;
+                        }
+                    }
+                    if damage > 0 && life_steal_percent > 0 {
+                        heals.push((entity, i32::max(1, damage * life_steal_percent / 100)));
+                    }
+
                     if damage == 0 {
-                        log.entries.push(format!(
+                        log.detail(format!(
                             "{} is unable to hurt {}.",
                             &name.name, &target_name.name
                         ));
@@ -95,10 +125,22 @@ impl<'a> System<'a> for MeleeCombatSystem {
                             &name.name, &target_name.name, damage
                         ));
                         SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                        if entity == *player_entity {
+                            run_info.damage_dealt += damage;
+                        }
+                        if wants_melee.target == *player_entity {
+                            run_info.last_damaged_by = Some(name.name.clone());
+                        }
                     }
                 }
             }
         }
+        for (entity, amount) in heals.iter() {
+            if let Some(stats) = combat_stats.get_mut(*entity) {
+                stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+            }
+        }
+
         wants_melee.clear();
     }
 }
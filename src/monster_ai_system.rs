@@ -54,14 +54,7 @@ impl<'a> System<'a> for MonsterAI {
                     confused.remove(entity);
                 }
                 can_act = false;
-                particle_builder.request(
-                    pos.x,
-                    pos.y,
-                    rltk::RGB::named(rltk::MAGENTA),
-                    rltk::RGB::named(rltk::BLACK),
-                    574,
-                    200.0,
-                )
+                particle_builder.request(pos.x, pos.y, "particle.confusion", 200.0)
             }
 
             if can_act {
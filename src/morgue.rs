@@ -0,0 +1,356 @@
+//! What's left of a run once the player dies: a morgue file describing how it went, and a place
+//! in the high-score table if it went well enough.
+
+use super::{
+    CombatStats, EquipmentSlot, Equipped, HungerClock, HungerState, InBackpack, Item, Map, Monster,
+    Name, ObfuscatedName, Player, Position, TileType, Wallet, gamelog::GameLog,
+    identification_system::get_item_display_name, run_info::RunInfo, saveload_system,
+};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::PathBuf;
+
+/// How many runs the high-score table remembers.
+pub const MAX_HIGH_SCORES: usize = 10;
+
+/// How many of the last log messages go in a morgue file.
+const MORGUE_LOG_LINES: usize = 12;
+
+/// One run in the high-score table.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScore {
+    pub character: String,
+    pub depth: i32,
+    pub turns: i32,
+    pub cause_of_death: String,
+    pub timestamp: i64,
+    pub seed: u64,
+}
+
+impl HighScore {
+    fn new(ecs: &World) -> HighScore {
+        let run_info = ecs.fetch::<RunInfo>();
+        HighScore {
+            character: run_info.character.clone(),
+            depth: ecs.fetch::<Map>().depth,
+            turns: run_info.turns,
+            cause_of_death: cause_of_death(&run_info),
+            timestamp: Local::now().timestamp(),
+            seed: run_info.seed,
+        }
+    }
+
+    pub fn died_on(&self) -> String {
+        match Local.timestamp_opt(self.timestamp, 0).single() {
+            Some(time) => time.format("%Y-%m-%d").to_string(),
+            None => "unknown date".to_string(),
+        }
+    }
+
+    /// Deeper runs rank higher, and of runs that reached the same depth, the quicker one.
+    fn beats(&self, other: &HighScore) -> bool {
+        self.depth > other.depth || (self.depth == other.depth && self.turns < other.turns)
+    }
+}
+
+/// The high-score table as it was when the high-score screen was opened, so it isn't read
+/// again every frame.
+pub struct HighScoreTable {
+    pub scores: Vec<HighScore>,
+}
+
+/// What was written when the player died, for the game over screen to point to.
+pub struct DeathRecord {
+    pub morgue_file: Option<PathBuf>,
+    /// The run's place in the high-score table, counting from zero, if it made it in.
+    pub rank: Option<usize>,
+}
+
+pub fn cause_of_death(run_info: &RunInfo) -> String {
+    run_info
+        .last_damaged_by
+        .clone()
+        .unwrap_or("unknown causes".to_string())
+}
+
+fn high_score_path() -> PathBuf {
+    saveload_system::data_dir().join("highscores.json")
+}
+
+/// The high-score table, best first. A missing or unreadable table is treated as empty.
+pub fn high_scores() -> Vec<HighScore> {
+    File::open(high_score_path())
+        .ok()
+        .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+        .unwrap_or_default()
+}
+
+/// Adds `score` to `scores` in order, returning where it placed if it made the table.
+fn insert_score(scores: &mut Vec<HighScore>, score: HighScore) -> Option<usize> {
+    let rank = scores
+        .iter()
+        .position(|other| score.beats(other))
+        .unwrap_or(scores.len());
+    if rank >= MAX_HIGH_SCORES {
+        return None;
+    }
+    scores.insert(rank, score);
+    scores.truncate(MAX_HIGH_SCORES);
+    Some(rank)
+}
+
+fn save_high_scores(scores: &[HighScore]) -> io::Result<()> {
+    let path = high_score_path();
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(scores)?)?;
+    fs::rename(temp_path, path)
+}
+
+/// Writes the dead player's morgue file and enters the run in the high-score table. Anything
+/// that can't be written is noted in the log.
+pub fn record_death(ecs: &mut World) {
+    fs::create_dir_all(saveload_system::data_dir().join("morgue")).ok();
+
+    let morgue_file = write_morgue(ecs);
+    let mut scores = high_scores();
+    let rank = if ecs.fetch::<RunInfo>().wizardry {
+        None
+    } else {
+        insert_score(&mut scores, HighScore::new(ecs))
+    };
+    let saved_scores = match rank {
+        Some(_) => save_high_scores(&scores),
+        None => Ok(()),
+    };
+
+    let mut log = ecs.fetch_mut::<GameLog>();
+    if let Err(e) = &morgue_file {
+        log.entries
+            .push(format!("Unable to write the morgue file: {}.", e));
+    }
+    if let Err(e) = saved_scores {
+        log.entries
+            .push(format!("Unable to save the high scores: {}.", e));
+    }
+    drop(log);
+    ecs.insert(DeathRecord {
+        morgue_file: morgue_file.ok(),
+        rank,
+    });
+}
+
+fn write_morgue(ecs: &World) -> io::Result<PathBuf> {
+    let run_info = ecs.fetch::<RunInfo>();
+    let path = saveload_system::data_dir().join("morgue").join(format!(
+        "{}-{}.txt",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        run_info.seed
+    ));
+    let mut file = File::create(&path)?;
+    file.write_all(morgue_text(ecs).as_bytes())?;
+    Ok(path)
+}
+
+/// The morgue file's contents: who the player was, what they carried, how they died and
+/// what the level looked like when they did.
+pub fn morgue_text(ecs: &World) -> String {
+    let player_entity = *ecs.fetch::<Entity>();
+    let run_info = ecs.fetch::<RunInfo>();
+    let map = ecs.fetch::<Map>();
+    let names = ecs.read_storage::<Name>();
+    let obfuscated_names = ecs.read_storage::<ObfuscatedName>();
+    let mut lines = Vec::new();
+
+    lines.push(format!(
+        "RogueSpace {} morgue file",
+        env!("CARGO_PKG_VERSION")
+    ));
+    lines.push(String::new());
+    lines.push(format!(
+        "{} was killed by {} on depth {}, after {} turns.",
+        run_info.character,
+        cause_of_death(&run_info),
+        map.depth,
+        run_info.turns
+    ));
+    lines.push(format!("Seed: {}", run_info.seed));
+    if run_info.wizardry {
+        lines.push("Changed in wizard mode, so not entered in the high scores.".to_string());
+    }
+    lines.push(String::new());
+
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(player_entity) {
+        lines.push(format!(
+            "HP: {}/{}  Power: {}  Defense: {}",
+            stats.hp, stats.max_hp, stats.power, stats.defense
+        ));
+    }
+    if let Some(wallet) = ecs.read_storage::<Wallet>().get(player_entity) {
+        lines.push(format!("Credits: {}", wallet.credits));
+    }
+    if let Some(clock) = ecs.read_storage::<HungerClock>().get(player_entity) {
+        let hunger = match clock.state {
+            HungerState::WellFed => "Well fed",
+            HungerState::Normal => "Normal",
+            HungerState::Hungry => "Hungry",
+            HungerState::Starving => "Starving",
+        };
+        lines.push(format!("Hunger: {}", hunger));
+    }
+    lines.push(format!(
+        "Items used: {}  Damage dealt: {}",
+        run_info.items_used, run_info.damage_dealt
+    ));
+
+    let entities = ecs.entities();
+    lines.push(String::new());
+    lines.push("Equipment:".to_string());
+    let equipped = ecs.read_storage::<Equipped>();
+    let mut worn = 0;
+    for (item, gear) in (&entities, &equipped).join() {
+        if gear.owner == player_entity {
+            let slot = match gear.slot {
+                EquipmentSlot::Melee => "Melee",
+                EquipmentSlot::Shield => "Shield",
+            };
+            let name = get_item_display_name(&names, &obfuscated_names, item);
+            lines.push(format!("  {}: {}", slot, name));
+            worn += 1;
+        }
+    }
+    if worn == 0 {
+        lines.push("  Nothing".to_string());
+    }
+
+    lines.push(String::new());
+    lines.push("Inventory:".to_string());
+    let backpack = ecs.read_storage::<InBackpack>();
+    let mut carried: Vec<String> = (&entities, &backpack)
+        .join()
+        .filter(|(_item, pack)| pack.owner == player_entity)
+        .map(|(item, _pack)| get_item_display_name(&names, &obfuscated_names, item))
+        .collect();
+    carried.sort();
+    if carried.is_empty() {
+        lines.push("  Nothing".to_string());
+    }
+    for name in carried.iter() {
+        lines.push(format!("  {}", name));
+    }
+
+    lines.push(String::new());
+    lines.push("Last messages:".to_string());
+    let log = ecs.fetch::<GameLog>();
+    let first = log.entries.len().saturating_sub(MORGUE_LOG_LINES);
+    for entry in log.entries.iter().skip(first) {
+        lines.push(format!("  {}", entry));
+    }
+
+    lines.push(String::new());
+    lines.push("The final level:".to_string());
+    lines.push(map_dump(ecs));
+    lines.join("\n")
+}
+
+/// The parts of the map the player had seen, with whatever they could see on it at the end.
+pub fn map_dump(ecs: &World) -> String {
+    let map = ecs.fetch::<Map>();
+    let mut glyphs: Vec<char> = map
+        .tiles
+        .iter()
+        .enumerate()
+        .map(|(idx, tile)| match (map.revealed_tiles[idx], tile) {
+            (false, _) => ' ',
+            (true, TileType::Wall) => '#',
+            (true, TileType::Floor) => '.',
+            (true, TileType::DownStairs) => '>',
+        })
+        .collect();
+
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let names = ecs.read_storage::<Name>();
+    let players = ecs.read_storage::<Player>();
+    let monsters = ecs.read_storage::<Monster>();
+    let items = ecs.read_storage::<Item>();
+    // Items first, so anything standing on them is drawn over the top
+    let mut seen: Vec<(Entity, &Position)> = (&entities, &positions)
+        .join()
+        .filter(|(_entity, pos)| map.visible_tiles[map.xy_idx(pos.x, pos.y)])
+        .collect();
+    seen.sort_by_key(|(entity, _pos)| items.get(*entity).is_none());
+    for (entity, pos) in seen {
+        let glyph = if players.get(entity).is_some() {
+            '@'
+        } else if items.get(entity).is_some() {
+            '*'
+        } else {
+            let initial = names
+                .get(entity)
+                .and_then(|name| name.name.chars().next())
+                .unwrap_or('?');
+            if monsters.get(entity).is_some() {
+                initial.to_ascii_lowercase()
+            } else {
+                initial.to_ascii_uppercase()
+            }
+        };
+        glyphs[map.xy_idx(pos.x, pos.y)] = glyph;
+    }
+
+    let rows: Vec<String> = glyphs
+        .chunks(map.width as usize)
+        .map(|row| row.iter().collect::<String>().trim_end().to_string())
+        .collect();
+    // Leave out the rows above and below everything that was seen
+    let first = rows.iter().position(|row| !row.is_empty()).unwrap_or(0);
+    let last = rows.iter().rposition(|row| !row.is_empty()).unwrap_or(0);
+    rows[first..=last].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RunState, State, new_world};
+
+    fn score(depth: i32, turns: i32) -> HighScore {
+        HighScore {
+            character: "Player".to_string(),
+            depth,
+            turns,
+            cause_of_death: "Goblin".to_string(),
+            timestamp: 0,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn high_scores_rank_by_depth_then_speed() {
+        let mut scores = Vec::new();
+        assert_eq!(insert_score(&mut scores, score(3, 500)), Some(0));
+        assert_eq!(insert_score(&mut scores, score(5, 900)), Some(0));
+        assert_eq!(insert_score(&mut scores, score(3, 400)), Some(1));
+        assert_eq!(insert_score(&mut scores, score(1, 50)), Some(3));
+
+        for _ in 0..MAX_HIGH_SCORES {
+            insert_score(&mut scores, score(4, 100));
+        }
+        assert_eq!(scores.len(), MAX_HIGH_SCORES);
+        assert_eq!(insert_score(&mut scores, score(2, 10)), None);
+        assert_eq!(scores[0].depth, 5);
+    }
+
+    #[test]
+    fn morgue_describes_the_run() {
+        let mut gs = State { ecs: new_world(5) };
+        gs.resolve(RunState::PreRun);
+        gs.ecs.write_resource::<RunInfo>().last_damaged_by = Some("Orc".to_string());
+        let text = morgue_text(&gs.ecs);
+        assert!(text.contains("killed by Orc on depth 1"));
+        assert!(text.contains("Welcome to RogueSpace"));
+        assert!(text.contains('@'));
+    }
+}
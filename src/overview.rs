@@ -0,0 +1,287 @@
+//! The explored level at a glance, shrunk so each cell on screen stands for a block of tiles
+//! and shows the most important thing in it: full-screen from the map overview, and in a corner
+//! of the map as the minimap.
+
+use super::{
+    Map, TileType, backend,
+    keymap::{self, Action, Context},
+    settings, theme,
+};
+use rltk::{Point, RGB, Rltk};
+use specs::prelude::*;
+
+/// The most cells the minimap may take up in the top right corner.
+const MINIMAP_WIDTH: i32 = 20;
+const MINIMAP_HEIGHT: i32 = 11;
+/// The most cells the full-screen overview may take up, inside its box and above the legend.
+const OVERVIEW_WIDTH: i32 = backend::SCREEN_WIDTH - 2;
+const OVERVIEW_HEIGHT: i32 = backend::SCREEN_HEIGHT - 6;
+/// The rows the legend and the stats go on, below the map.
+const LEGEND_ROW: i32 = OVERVIEW_HEIGHT + 2;
+const STATS_ROW: i32 = LEGEND_ROW + 1;
+
+/// What a cell shows, from least to most important.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Mark {
+    Unexplored,
+    Wall,
+    Floor,
+    Item,
+    Stairs,
+    Player,
+}
+
+impl Mark {
+    fn look(self) -> &'static str {
+        match self {
+            Mark::Unexplored => "unknown",
+            Mark::Wall => "wall",
+            Mark::Floor => "floor",
+            Mark::Item => "ui.map.item",
+            Mark::Stairs => "stairs.down",
+            Mark::Player => "player",
+        }
+    }
+
+    fn glyph(self) -> char {
+        match self {
+            Mark::Unexplored => ' ',
+            Mark::Wall => '#',
+            Mark::Floor => '.',
+            Mark::Item => '*',
+            Mark::Stairs => '>',
+            Mark::Player => '@',
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Mark::Unexplored => "Unexplored",
+            Mark::Wall => "Wall",
+            Mark::Floor => "Floor",
+            Mark::Item => "Items and the like",
+            Mark::Stairs => "Stairs",
+            Mark::Player => "You",
+        }
+    }
+}
+
+/// How many tiles across and down each cell stands for, so the whole of `map` fits in `width`
+/// by `height` cells.
+pub fn scale_to_fit(map: &Map, width: i32, height: i32) -> i32 {
+    (map.width as u32)
+        .div_ceil(width as u32)
+        .max((map.height as u32).div_ceil(height as u32))
+        .max(1) as i32
+}
+
+/// A row of marks for each row of cells, with each cell standing for `scale` by `scale` tiles.
+pub fn marks(ecs: &World, scale: i32) -> Vec<Vec<Mark>> {
+    let map = ecs.fetch::<Map>();
+    let columns = (map.width as u32).div_ceil(scale as u32) as usize;
+    let rows = (map.height as u32).div_ceil(scale as u32) as usize;
+    let mut marks = vec![vec![Mark::Unexplored; columns]; rows];
+    let mut mark = |x: i32, y: i32, mark: Mark| {
+        let cell = &mut marks[(y / scale) as usize][(x / scale) as usize];
+        *cell = (*cell).max(mark);
+    };
+
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        if map.revealed_tiles[idx] {
+            let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+            mark(
+                x,
+                y,
+                match tile {
+                    TileType::Wall => Mark::Wall,
+                    TileType::Floor => Mark::Floor,
+                    TileType::DownStairs => Mark::Stairs,
+                },
+            );
+        }
+    }
+    for idx in map.remembered.keys() {
+        mark(*idx as i32 % map.width, *idx as i32 / map.width, Mark::Item);
+    }
+    let player_pos = ecs.fetch::<Point>();
+    mark(player_pos.x, player_pos.y, Mark::Player);
+
+    marks
+}
+
+/// Draws the minimap in the top right corner of the map, if it's turned on. Every cell is a
+/// solid block, so the map beneath doesn't show through.
+pub fn draw_minimap(ecs: &World, ctx: &mut Rltk) {
+    if !settings::current().minimap {
+        return;
+    }
+    let theme = theme::current();
+    let scale = scale_to_fit(&ecs.fetch::<Map>(), MINIMAP_WIDTH, MINIMAP_HEIGHT);
+    let marks = marks(ecs, scale);
+    let left = backend::SCREEN_WIDTH - marks[0].len() as i32;
+
+    backend::set_layer(ctx, backend::OVERLAY_LAYER);
+    backend::use_tileset(ctx, false);
+    for (y, row) in marks.iter().enumerate() {
+        for (x, mark) in row.iter().enumerate() {
+            let fg = match mark {
+                Mark::Unexplored => RGB::from_f32(0.1, 0.1, 0.1),
+                Mark::Floor => theme.look(mark.look()).fg * 0.5,
+                mark => theme.look(mark.look()).fg,
+            };
+            ctx.set(
+                left + x as i32,
+                y as i32,
+                fg,
+                RGB::named(rltk::BLACK),
+                rltk::to_cp437('█'),
+            );
+        }
+    }
+}
+
+/// Shows the whole explored level, shrunk to fit the screen if need be. Returns true once the
+/// player closes it.
+pub fn overview(ecs: &World, ctx: &mut Rltk) -> bool {
+    let theme = theme::current();
+    let map = ecs.fetch::<Map>();
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.draw_box(
+        0,
+        0,
+        backend::SCREEN_WIDTH - 1,
+        backend::SCREEN_HEIGHT - 1,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color_centered(
+        0,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(" Map of depth {} ", map.depth),
+    );
+
+    let scale = scale_to_fit(&map, OVERVIEW_WIDTH, OVERVIEW_HEIGHT);
+    let marks = marks(ecs, scale);
+    let left = 1 + (OVERVIEW_WIDTH - marks[0].len() as i32) / 2;
+    let top = 1 + (OVERVIEW_HEIGHT - marks.len() as i32) / 2;
+    for (y, row) in marks.iter().enumerate() {
+        for (x, mark) in row.iter().enumerate() {
+            if *mark != Mark::Unexplored {
+                ctx.set(
+                    left + x as i32,
+                    top + y as i32,
+                    theme.look(mark.look()).fg,
+                    RGB::named(rltk::BLACK),
+                    rltk::to_cp437(mark.glyph()),
+                );
+            }
+        }
+    }
+
+    let mut x = 2;
+    for mark in [
+        Mark::Player,
+        Mark::Stairs,
+        Mark::Item,
+        Mark::Floor,
+        Mark::Wall,
+    ] {
+        ctx.set(
+            x,
+            LEGEND_ROW,
+            theme.look(mark.look()).fg,
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(mark.glyph()),
+        );
+        ctx.print(x + 2, LEGEND_ROW, mark.description());
+        x += mark.description().len() as i32 + 5;
+    }
+    let open: Vec<bool> = map
+        .tiles
+        .iter()
+        .zip(map.revealed_tiles.iter())
+        .filter(|(tile, _revealed)| **tile != TileType::Wall)
+        .map(|(_tile, revealed)| *revealed)
+        .collect();
+    let explored = open.iter().filter(|revealed| **revealed).count();
+    ctx.print(
+        2,
+        STATS_ROW,
+        format!(
+            "Explored: {}%    Scale: 1:{}    {} or {}: close",
+            explored * 100 / open.len().max(1),
+            scale,
+            keymap::describe(Action::Overview),
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+
+    keymap::pressed(ctx, Context::Menu) == Some(Action::MenuBack)
+        || keymap::pressed(ctx, Context::Game) == Some(Action::Overview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{MAP_HEIGHT, MAP_WIDTH};
+    use crate::tile_memory_system::TileMemorySystem;
+    use crate::{Item, Position, new_world};
+
+    #[test]
+    fn cells_show_the_most_important_tile() {
+        let ecs = new_world(9);
+        let player_pos = *ecs.fetch::<Point>();
+        {
+            let mut map = ecs.fetch_mut::<Map>();
+            map.revealed_tiles.fill(true);
+            assert_eq!(scale_to_fit(&map, MINIMAP_WIDTH, MINIMAP_HEIGHT), 4);
+            assert_eq!(scale_to_fit(&map, MAP_WIDTH as i32, MAP_HEIGHT as i32), 1);
+        }
+
+        let marks = marks(&ecs, 4);
+        assert_eq!((marks[0].len(), marks.len()), (20, 11));
+        assert_eq!(
+            marks[(player_pos.y / 4) as usize][(player_pos.x / 4) as usize],
+            Mark::Player
+        );
+        assert!(marks.iter().flatten().any(|mark| *mark == Mark::Stairs));
+        assert!(!marks.iter().flatten().any(|mark| *mark == Mark::Unexplored));
+
+        let map = ecs.fetch::<Map>();
+        let full_size = super::marks(&ecs, 1);
+        assert_eq!(full_size.len(), map.height as usize);
+        assert_eq!(full_size[0].len(), map.width as usize);
+    }
+
+    #[test]
+    fn items_are_remembered_until_seen_again() {
+        let ecs = new_world(9);
+        let (item, pos) = {
+            let entities = ecs.entities();
+            let items = ecs.read_storage::<Item>();
+            let positions = ecs.read_storage::<Position>();
+            (&entities, &items, &positions)
+                .join()
+                .map(|(entity, _item, pos)| (entity, Point::new(pos.x, pos.y)))
+                .next()
+                .expect("Level has no items")
+        };
+        let idx = ecs.fetch::<Map>().xy_idx(pos.x, pos.y);
+        let see = |visible: bool| {
+            ecs.fetch_mut::<Map>().visible_tiles.fill(visible);
+            TileMemorySystem {}.run_now(&ecs);
+        };
+
+        see(true);
+        assert!(ecs.fetch::<Map>().remembered.contains_key(&idx));
+        assert_eq!(marks(&ecs, 1)[pos.y as usize][pos.x as usize], Mark::Item);
+
+        // Taken while out of sight, it's remembered where it was
+        ecs.write_storage::<Position>().remove(item);
+        see(false);
+        assert!(ecs.fetch::<Map>().remembered.contains_key(&idx));
+        see(true);
+        assert!(!ecs.fetch::<Map>().remembered.contains_key(&idx));
+    }
+}
@@ -1,109 +1,48 @@
-use super::{ParticleLifetime, Position, Renderable, Rltk};
-use rltk::RGB;
 use specs::prelude::*;
 
-struct ParticleRequest {
-    x: i32,
-    y: i32,
-    fg: RGB,
-    bg: RGB,
-    glyph: rltk::FontCharType,
-    lifetime: f32,
+/// A short-lived visual effect, such as a hit flash. Particles are purely cosmetic, so they are
+/// kept here rather than as entities; otherwise the frame rate would decide which entity ids
+/// the game hands out, and replays would drift from the games they recorded.
+pub struct Particle {
+    pub x: i32,
+    pub y: i32,
+    /// What the particle looks like, by name; see `theme`.
+    pub look: &'static str,
+    lifetime_ms: f32,
 }
 
 pub struct ParticleBuilder {
-    requests: Vec<ParticleRequest>,
+    particles: Vec<Particle>,
 }
 impl ParticleBuilder {
     #[allow(clippy::new_without_default)]
     pub fn new() -> ParticleBuilder {
         ParticleBuilder {
-            requests: Vec::new(),
+            particles: Vec::new(),
         }
     }
 
-    pub fn request(
-        &mut self,
-        x: i32,
-        y: i32,
-        fg: RGB,
-        bg: RGB,
-        glyph: rltk::FontCharType,
-        lifetime: f32,
-    ) {
-        self.requests.push(ParticleRequest {
+    pub fn request(&mut self, x: i32, y: i32, look: &'static str, lifetime: f32) {
+        self.particles.push(Particle {
             x,
             y,
-            fg,
-            bg,
-            glyph,
-            lifetime,
+            look,
+            lifetime_ms: lifetime,
         });
     }
-}
-
-pub struct ParticleSpawnSystem {}
-impl<'a> System<'a> for ParticleSpawnSystem {
-    #[allow(clippy::type_complexity)]
-    type SystemData = (
-        Entities<'a>,
-        WriteStorage<'a, Position>,
-        WriteStorage<'a, Renderable>,
-        WriteStorage<'a, ParticleLifetime>,
-        WriteExpect<'a, ParticleBuilder>,
-    );
-
-    fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut positions, mut renderables, mut particles, mut particle_builder) = data;
-        for new_particle in particle_builder.requests.iter() {
-            let p = entities.create();
-            positions
-                .insert(
-                    p,
-                    Position {
-                        x: new_particle.x,
-                        y: new_particle.y,
-                    },
-                )
-                .expect("Unable to insert position");
-            renderables
-                .insert(
-                    p,
-                    Renderable {
-                        fg: new_particle.fg,
-                        bg: new_particle.bg,
-                        glyph: new_particle.glyph,
-                        render_order: 0,
-                    },
-                )
-                .expect("Unable to insert renderable");
-            particles
-                .insert(
-                    p,
-                    ParticleLifetime {
-                        lifetime_ms: new_particle.lifetime,
-                    },
-                )
-                .expect("Unable to insert lifetime");
-        }
 
-        particle_builder.requests.clear();
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
     }
 }
 
-pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
-    let mut dead_particles: Vec<Entity> = Vec::new();
-    {
-        let mut particles = ecs.write_storage::<ParticleLifetime>();
-        let entities = ecs.entities();
-        for (entity, particle) in (&entities, &mut particles).join() {
-            particle.lifetime_ms -= ctx.frame_time_ms;
-            if particle.lifetime_ms < 0.0 {
-                dead_particles.push(entity);
-            }
-        }
-    }
-    for dead in dead_particles.iter() {
-        ecs.delete_entity(*dead).expect("Particle will not die");
+/// Ages every particle by `elapsed_ms`, removing those that have burned out.
+pub fn cull_dead_particles(ecs: &mut World, elapsed_ms: f32) {
+    let mut builder = ecs.write_resource::<ParticleBuilder>();
+    for particle in builder.particles.iter_mut() {
+        particle.lifetime_ms -= elapsed_ms;
     }
+    builder
+        .particles
+        .retain(|particle| particle.lifetime_ms >= 0.0);
 }
@@ -1,11 +1,26 @@
 use super::{
-    CombatStats, HungerClock, HungerState, Item, Map, Monster, Player, Position, RunState, State,
-    TileType, Viewshed, WantsToMelee, WantsToPickupItem, gamelog::GameLog,
+    CombatStats, Consumable, Currency, HungerClock, HungerState, Item, LootContainer, Map, Monster,
+    Player, Position, RunState, State, TileType, Vendor, Viewshed, WantsToDropItem, WantsToMelee,
+    WantsToOpenContainer, WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
+    gamelog::GameLog,
+    gui::SlotMenuMode,
+    gui::VendorMode,
+    keymap::{self, Action, Context},
+    replay, saveload_system, settings,
+    settings::AutoPickup,
+    vendor, wizard,
 };
-use rltk::{Point, Rltk, VirtualKeyCode};
+use rltk::{Point, Rltk};
 use specs::prelude::*;
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+/// Moves the player, or attacks or trades with whatever's in the way. Once moved, they pick up
+/// the first item there that `pick_up` covers.
+pub fn try_move_player(
+    delta_x: i32,
+    delta_y: i32,
+    pick_up: AutoPickup,
+    ecs: &mut World,
+) -> RunState {
     let mut positions = ecs.write_storage::<Position>();
     let mut players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
@@ -13,6 +28,13 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
     let map = ecs.fetch::<Map>();
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let vendors = ecs.read_storage::<Vendor>();
+    let containers = ecs.read_storage::<LootContainer>();
+    let mut wants_to_open = ecs.write_storage::<WantsToOpenContainer>();
+    let items = ecs.read_storage::<Item>();
+    let currencies = ecs.read_storage::<Currency>();
+    let consumables = ecs.read_storage::<Consumable>();
+    let mut wants_pickup = ecs.write_storage::<WantsToPickupItem>();
 
     for (entity, _player, pos, viewshed) in
         (&entities, &mut players, &mut positions, &mut viewsheds).join()
@@ -22,11 +44,27 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             || pos.y + delta_y < 1
             || pos.y + delta_y > map.height - 1
         {
-            return;
+            return RunState::PlayerTurn;
         }
         let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
 
         for potential_target in map.tile_content[destination_idx].iter() {
+            if vendors.get(*potential_target).is_some() {
+                return RunState::ShowVendor {
+                    mode: VendorMode::Sell,
+                };
+            }
+            if containers.get(*potential_target).is_some() {
+                wants_to_open
+                    .insert(
+                        entity,
+                        WantsToOpenContainer {
+                            container: *potential_target,
+                        },
+                    )
+                    .expect("Unable to insert intent");
+                return RunState::PlayerTurn;
+            }
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
                 wants_to_melee
@@ -37,7 +75,7 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
                         },
                     )
                     .expect("Add target failed");
-                return;
+                return RunState::PlayerTurn;
             }
         }
 
@@ -49,53 +87,181 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
             let mut ppos = ecs.write_resource::<Point>();
             ppos.x = pos.x;
             ppos.y = pos.y;
+
+            let found = map.tile_content[destination_idx].iter().find(|item| {
+                items.get(**item).is_some()
+                    && pick_up.picks_up(
+                        currencies.get(**item).is_some(),
+                        consumables.get(**item).is_some(),
+                    )
+            });
+            if let Some(item) = found {
+                wants_pickup
+                    .insert(
+                        entity,
+                        WantsToPickupItem {
+                            collected_by: entity,
+                            item: *item,
+                        },
+                    )
+                    .expect("Unable to insert want to pickup");
+            }
         }
     }
+    RunState::PlayerTurn
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    if ctx.shift {
-        match ctx.key {
-            Some(VirtualKeyCode::Period) => {
-                if try_next_level(&mut gs.ecs) {
-                    return RunState::NextLevel;
-                }
-            }
-            None | Some(_) => return RunState::AwaitingInput,
+/// Something the player does with their turn, however it was chosen: by a key press, from a
+/// menu, or by a script.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum PlayerAction {
+    /// Picks up whatever `pick_up` covers at the destination too.
+    Move {
+        delta_x: i32,
+        delta_y: i32,
+        pick_up: AutoPickup,
+    },
+    Wait,
+    PickUp,
+    Descend,
+    UseItem {
+        item: Entity,
+        target: Option<Point>,
+    },
+    DropItem {
+        item: Entity,
+    },
+    RemoveItem {
+        item: Entity,
+    },
+    /// Sells an item to the quartermaster being traded with.
+    Sell {
+        item: Entity,
+    },
+    /// Buys `vendor::STOCK[stock]` from the quartermaster being traded with.
+    Buy {
+        stock: usize,
+    },
+}
+
+/// Carries out `action` for the player, returning the state the game moves to. Actions that
+/// turn out to be impossible, such as descending away from the stairs, don't use up a turn.
+/// Every action is recorded, so the run can be replayed.
+pub fn perform_action(ecs: &mut World, action: PlayerAction) -> RunState {
+    replay::record(ecs, action);
+    match action {
+        PlayerAction::Move {
+            delta_x,
+            delta_y,
+            pick_up,
+        } => try_move_player(delta_x, delta_y, pick_up, ecs),
+        PlayerAction::Wait => skip_turn(ecs),
+        PlayerAction::PickUp => {
+            get_item(ecs);
+            RunState::PlayerTurn
         }
-    }
-    match ctx.key {
-        None => return RunState::AwaitingInput,
-        Some(key) => match key {
-            // Movement
-            VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs)
-            }
-            VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs)
+        PlayerAction::Descend => {
+            if try_next_level(ecs) {
+                RunState::NextLevel
+            } else {
+                RunState::AwaitingInput
             }
-            VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs)
+        }
+        PlayerAction::UseItem { item, target } => {
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToUseItem>()
+                .insert(player_entity, WantsToUseItem { item, target })
+                .expect("Unable to insert intent");
+            RunState::PlayerTurn
+        }
+        PlayerAction::DropItem { item } => {
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToDropItem>()
+                .insert(player_entity, WantsToDropItem { item })
+                .expect("Unable to insert intent");
+            RunState::PlayerTurn
+        }
+        PlayerAction::RemoveItem { item } => {
+            let player_entity = *ecs.fetch::<Entity>();
+            ecs.write_storage::<WantsToRemoveItem>()
+                .insert(player_entity, WantsToRemoveItem { item })
+                .expect("Unable to insert intent");
+            RunState::PlayerTurn
+        }
+        PlayerAction::Sell { item } => {
+            vendor::sell_item(ecs, item);
+            RunState::ShowVendor {
+                mode: VendorMode::Sell,
             }
-            VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs)
+        }
+        PlayerAction::Buy { stock } => {
+            let (name, price) = vendor::STOCK[stock];
+            vendor::buy_item(ecs, name, price);
+            RunState::ShowVendor {
+                mode: VendorMode::Buy,
             }
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::U => try_move_player(1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::Y => try_move_player(-1, -1, &mut gs.ecs),
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(1, 1, &mut gs.ecs),
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
-            VirtualKeyCode::Numpad5 | VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
-            // Actions
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
-            VirtualKeyCode::I => return RunState::ShowInventory,
-            VirtualKeyCode::D => return RunState::ShowDropItem,
-            VirtualKeyCode::R => return RunState::ShowRemoveItem,
-            // Menu
-            VirtualKeyCode::Escape => return RunState::SaveGame,
-            _ => return RunState::AwaitingInput,
+        }
+    }
+}
+
+fn move_action(delta_x: i32, delta_y: i32) -> Option<PlayerAction> {
+    Some(PlayerAction::Move {
+        delta_x,
+        delta_y,
+        pick_up: settings::current().auto_pickup,
+    })
+}
+
+/// The turn `action` stands for, if it takes one.
+fn turn_action(action: Action) -> Option<PlayerAction> {
+    match action {
+        Action::MoveNorth => move_action(0, -1),
+        Action::MoveSouth => move_action(0, 1),
+        Action::MoveWest => move_action(-1, 0),
+        Action::MoveEast => move_action(1, 0),
+        Action::MoveNorthWest => move_action(-1, -1),
+        Action::MoveNorthEast => move_action(1, -1),
+        Action::MoveSouthWest => move_action(-1, 1),
+        Action::MoveSouthEast => move_action(1, 1),
+        Action::Wait => Some(PlayerAction::Wait),
+        Action::PickUp => Some(PlayerAction::PickUp),
+        Action::Descend => Some(PlayerAction::Descend),
+        _ => None,
+    }
+}
+
+pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let Some(action) = keymap::pressed(ctx, Context::Game) else {
+        return RunState::AwaitingInput;
+    };
+    if let Some(action) = turn_action(action) {
+        if confirmation(&gs.ecs, action).is_some() {
+            return RunState::ConfirmAction { action };
+        }
+        return perform_action(&mut gs.ecs, action);
+    }
+    match action {
+        // Menus
+        Action::Inventory => RunState::ShowInventory,
+        Action::Drop => RunState::ShowDropItem,
+        Action::Remove => RunState::ShowRemoveItem,
+        Action::Save => RunState::SaveSlots {
+            mode: SlotMenuMode::Save,
+            selection: saveload_system::first_free_slot().unwrap_or(0),
         },
+        Action::Overview => RunState::MapOverview,
+        Action::Help => RunState::Help,
+        Action::WizardConsole if wizard::is_enabled() => RunState::WizardConsole,
+        // Display
+        Action::SwitchTheme => {
+            let theme = settings::cycle_theme(true);
+            let mut log = gs.ecs.fetch_mut::<GameLog>();
+            log.entries
+                .push(format!("Switched to the {} theme.", theme.name));
+            RunState::AwaitingInput
+        }
+        _ => RunState::AwaitingInput,
     }
-    RunState::PlayerTurn
 }
 
 fn get_item(ecs: &mut World) {
@@ -131,6 +297,27 @@ fn get_item(ecs: &mut World) {
     }
 }
 
+/// The question to ask before `action` is carried out, if it can't be taken back and the
+/// player wants to be asked.
+pub fn confirmation(ecs: &World, action: PlayerAction) -> Option<String> {
+    if !settings::current().confirmations {
+        return None;
+    }
+    let map = ecs.fetch::<Map>();
+    let player_pos = ecs.fetch::<Point>();
+    match action {
+        PlayerAction::Descend
+            if map.tiles[map.xy_idx(player_pos.x, player_pos.y)] == TileType::DownStairs =>
+        {
+            Some(format!(
+                "Descend to depth {}? There's no way back up.",
+                map.depth + 1
+            ))
+        }
+        _ => None,
+    }
+}
+
 pub fn try_next_level(ecs: &mut World) -> bool {
     let player_pos = ecs.fetch::<Point>();
     let map = ecs.fetch::<Map>();
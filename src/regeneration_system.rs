@@ -0,0 +1,35 @@
+use super::{CombatStats, Equipped, Regeneration, RunState};
+use specs::prelude::*;
+
+pub struct RegenerationSystem {}
+impl<'a> System<'a> for RegenerationSystem {
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteStorage<'a, Regeneration>,
+        ReadStorage<'a, Equipped>,
+        WriteStorage<'a, CombatStats>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (player_entity, runstate, mut regeneration, equipped, mut combat_stats) = data;
+
+        for (regen, equipped_by) in (&mut regeneration, &equipped).join() {
+            let proceed = match *runstate {
+                RunState::PlayerTurn => equipped_by.owner == *player_entity,
+                RunState::MonsterTurn => equipped_by.owner != *player_entity,
+                _ => false,
+            };
+
+            if proceed {
+                regen.countdown -= 1;
+                if regen.countdown < 1 {
+                    regen.countdown = regen.interval;
+                    if let Some(stats) = combat_stats.get_mut(equipped_by.owner) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + regen.amount);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Records every action the player takes, so a run can be watched again exactly as it was
+//! played. Given the same seed and the same actions, the game always plays out the same way.
+
+use super::{
+    MAP_HEIGHT, MAP_WIDTH, RunState, SerializeMe, State,
+    gamelog::GameLog,
+    gui,
+    keymap::{self, Action, Context},
+    new_world, new_world_of_size,
+    player::PlayerAction,
+    player::perform_action,
+    run_info::RunInfo,
+    saveload_system,
+    settings::AutoPickup,
+};
+use rltk::{Point, RGB, Rltk};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{Marker, SimpleMarker};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// How long each command is shown for when watching a replay at normal speed.
+const COMMAND_MS: f32 = 150.0;
+
+/// How many commands fast-forward plays through each frame.
+const FAST_FORWARD_COMMANDS: usize = 25;
+
+/// An action as it's stored in a replay. Entities are referred to by their save marker, which
+/// is the same every time the run is played, unlike the entity itself.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Command {
+    Move {
+        delta_x: i32,
+        delta_y: i32,
+        #[serde(default)]
+        pick_up: AutoPickup,
+    },
+    Wait,
+    PickUp,
+    Descend,
+    UseItem {
+        item: u64,
+        target: Option<(i32, i32)>,
+    },
+    DropItem {
+        item: u64,
+    },
+    RemoveItem {
+        item: u64,
+    },
+    Sell {
+        item: u64,
+    },
+    Buy {
+        stock: usize,
+    },
+    /// The run was saved here, and carried on after being loaded again.
+    Reload,
+}
+
+impl Command {
+    /// The command for `action`, or `None` if it involves an entity that has no save marker.
+    fn from_action(ecs: &World, action: PlayerAction) -> Option<Command> {
+        let marker = |entity: Entity| {
+            ecs.read_storage::<SimpleMarker<SerializeMe>>()
+                .get(entity)
+                .map(|marker| marker.id())
+        };
+        Some(match action {
+            PlayerAction::Move {
+                delta_x,
+                delta_y,
+                pick_up,
+            } => Command::Move {
+                delta_x,
+                delta_y,
+                pick_up,
+            },
+            PlayerAction::Wait => Command::Wait,
+            PlayerAction::PickUp => Command::PickUp,
+            PlayerAction::Descend => Command::Descend,
+            PlayerAction::UseItem { item, target } => Command::UseItem {
+                item: marker(item)?,
+                target: target.map(|point| (point.x, point.y)),
+            },
+            PlayerAction::DropItem { item } => Command::DropItem {
+                item: marker(item)?,
+            },
+            PlayerAction::RemoveItem { item } => Command::RemoveItem {
+                item: marker(item)?,
+            },
+            PlayerAction::Sell { item } => Command::Sell {
+                item: marker(item)?,
+            },
+            PlayerAction::Buy { stock } => Command::Buy { stock },
+        })
+    }
+
+    /// The action this command stands for in `ecs`, or `None` if it refers to an entity that
+    /// doesn't exist, which means the replay has gone astray.
+    fn to_action(&self, ecs: &World) -> Option<PlayerAction> {
+        let entity = |marker: u64| {
+            let entities = ecs.entities();
+            let markers = ecs.read_storage::<SimpleMarker<SerializeMe>>();
+            (&entities, &markers)
+                .join()
+                .find(|(_entity, m)| m.id() == marker)
+                .map(|(entity, _m)| entity)
+        };
+        Some(match self {
+            Command::Move {
+                delta_x,
+                delta_y,
+                pick_up,
+            } => PlayerAction::Move {
+                delta_x: *delta_x,
+                delta_y: *delta_y,
+                pick_up: *pick_up,
+            },
+            Command::Wait => PlayerAction::Wait,
+            Command::PickUp => PlayerAction::PickUp,
+            Command::Descend => PlayerAction::Descend,
+            Command::UseItem { item, target } => PlayerAction::UseItem {
+                item: entity(*item)?,
+                target: target.map(|(x, y)| Point::new(x, y)),
+            },
+            Command::DropItem { item } => PlayerAction::DropItem {
+                item: entity(*item)?,
+            },
+            Command::RemoveItem { item } => PlayerAction::RemoveItem {
+                item: entity(*item)?,
+            },
+            Command::Sell { item } => PlayerAction::Sell {
+                item: entity(*item)?,
+            },
+            Command::Buy { stock } => PlayerAction::Buy { stock: *stock },
+            Command::Reload => return None,
+        })
+    }
+}
+
+/// Every command of the run so far, along with the seed it began from. It is kept in saves,
+/// so a run that is saved and loaded can still be replayed from the start.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recording {
+    pub seed: u64,
+    /// The width and height of the run's levels.
+    #[serde(default = "full_size")]
+    pub map_size: (i32, i32),
+    pub commands: Vec<Command>,
+    /// Runs carried over from saves made before recordings were kept, or changed through the
+    /// wizard console, can't be replayed.
+    pub complete: bool,
+}
+
+impl Recording {
+    pub fn new(seed: u64, map_size: (i32, i32)) -> Recording {
+        Recording {
+            seed,
+            map_size,
+            commands: Vec::new(),
+            complete: true,
+        }
+    }
+}
+
+/// The size of runs recorded before levels could be any other.
+fn full_size() -> (i32, i32) {
+    (MAP_WIDTH as i32, MAP_HEIGHT as i32)
+}
+
+pub fn record(ecs: &mut World, action: PlayerAction) {
+    let command = Command::from_action(ecs, action);
+    let mut recording = ecs.write_resource::<Recording>();
+    match command {
+        Some(command) => recording.commands.push(command),
+        None => recording.complete = false,
+    }
+}
+
+/// Notes that the run was just loaded from a save.
+pub fn record_reload(ecs: &mut World) {
+    ecs.write_resource::<Recording>()
+        .commands
+        .push(Command::Reload);
+}
+
+/// A recording as it's written to disk.
+#[derive(Serialize, Deserialize)]
+pub struct ReplayFile {
+    /// The version of the game that made the recording; other versions may play it differently.
+    pub version: String,
+    pub seed: u64,
+    #[serde(default = "full_size")]
+    pub map_size: (i32, i32),
+    pub commands: Vec<Command>,
+}
+
+impl ReplayFile {
+    pub fn new(recording: &Recording) -> ReplayFile {
+        ReplayFile {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: recording.seed,
+            map_size: recording.map_size,
+            commands: recording.commands.clone(),
+        }
+    }
+}
+
+fn replay_dir() -> PathBuf {
+    saveload_system::data_dir().join("replays")
+}
+
+/// Writes the run's recording to the replays directory, replacing any earlier copy of the same
+/// run. Nothing is written while watching a replay, or for runs that can't be replayed.
+pub fn save_replay(ecs: &World) -> io::Result<()> {
+    if is_replaying(ecs) {
+        return Ok(());
+    }
+    let recording = ecs.fetch::<Recording>();
+    if !recording.complete {
+        return Ok(());
+    }
+    fs::create_dir_all(replay_dir())?;
+    let path = replay_dir().join(format!("run-{}.json", recording.seed));
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    serde_json::to_writer(&mut writer, &ReplayFile::new(&recording))?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(temp_path, path)
+}
+
+pub fn read_replay(path: &Path) -> io::Result<ReplayFile> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// A replay being watched: the commands still to come, and how they're being shown.
+pub struct Playback {
+    commands: VecDeque<Command>,
+    paused: bool,
+    fast_forward: bool,
+    wait_ms: f32,
+}
+
+pub fn is_replaying(ecs: &World) -> bool {
+    ecs.has_value::<Playback>()
+}
+
+/// Builds the world a replay starts from, ready to play it back.
+pub fn start_playback(replay: ReplayFile) -> World {
+    let mut ecs = new_world_of_size(replay.seed, replay.map_size);
+    if replay.version != env!("CARGO_PKG_VERSION") {
+        ecs.fetch_mut::<GameLog>().entries.push(format!(
+            "This replay was recorded by version {}, and may not play back faithfully.",
+            replay.version
+        ));
+    }
+    ecs.insert(Playback {
+        commands: replay.commands.into(),
+        paused: false,
+        fast_forward: false,
+        wait_ms: 0.0,
+    });
+    ecs
+}
+
+/// Plays all of `replay` at once, without a window, stopping early if the player dies.
+pub fn play_to_end(replay: ReplayFile) -> State {
+    let mut gs = State {
+        ecs: start_playback(replay),
+    };
+    gs.resolve(RunState::PreRun);
+    while !gs.ecs.fetch::<Playback>().commands.is_empty()
+        && fast_forward(&mut gs, 100) == RunState::AwaitingInput
+    {}
+    gs
+}
+
+/// Stands in for the player while a replay is being watched, which can be paused, sped up or
+/// left for the main menu.
+pub fn playback_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    {
+        let mut playback = gs.ecs.write_resource::<Playback>();
+        match keymap::pressed(ctx, Context::Replay) {
+            Some(Action::ReplayLeave) => {
+                drop(playback);
+                gs.ecs = new_world(RunInfo::random_seed());
+                return RunState::MainMenu {
+                    menu_selection: gui::MainMenuSelection::NewGame,
+                };
+            }
+            Some(Action::ReplayPause) => playback.paused = !playback.paused,
+            Some(Action::ReplayFastForward) => playback.fast_forward = !playback.fast_forward,
+            _ => {}
+        }
+
+        let leave = format!("{}: leave", keymap::describe(Action::ReplayLeave));
+        let pause = keymap::describe(Action::ReplayPause);
+        let speed = keymap::describe(Action::ReplayFastForward);
+        let status = if playback.commands.is_empty() {
+            format!(" Replay finished - {} ", leave)
+        } else if playback.paused {
+            format!(" Replay paused - {}: resume  {} ", pause, leave)
+        } else if playback.fast_forward {
+            format!(
+                " Replay - {}: pause  {}: normal speed  {} ",
+                pause, speed, leave
+            )
+        } else {
+            format!(
+                " Replay - {}: pause  {}: fast-forward  {} ",
+                pause, speed, leave
+            )
+        };
+        ctx.print_color(
+            2,
+            49,
+            RGB::named(rltk::CYAN),
+            RGB::named(rltk::BLACK),
+            status,
+        );
+
+        if playback.paused || playback.commands.is_empty() {
+            return RunState::AwaitingInput;
+        }
+        if playback.fast_forward {
+            drop(playback);
+            return fast_forward(gs, FAST_FORWARD_COMMANDS);
+        }
+        playback.wait_ms += ctx.frame_time_ms;
+        if playback.wait_ms < COMMAND_MS {
+            return RunState::AwaitingInput;
+        }
+        playback.wait_ms = 0.0;
+    }
+    play_command(gs)
+}
+
+/// Plays through up to `count` commands at once, resolving each turn without waiting for
+/// frames to go by. Stops early if the player dies.
+pub fn fast_forward(gs: &mut State, count: usize) -> RunState {
+    let mut state = RunState::AwaitingInput;
+    for _ in 0..count {
+        if gs.ecs.fetch::<Playback>().commands.is_empty() {
+            break;
+        }
+        state = play_command(gs);
+        state = gs.resolve(state);
+        if state != RunState::AwaitingInput {
+            break;
+        }
+    }
+    state
+}
+
+/// Carries out the next command, returning the state the game moves to.
+fn play_command(gs: &mut State) -> RunState {
+    let Some(command) = gs.ecs.write_resource::<Playback>().commands.pop_front() else {
+        return RunState::AwaitingInput;
+    };
+    let state = match command {
+        Command::Reload => reload(gs),
+        command => match command.to_action(&gs.ecs) {
+            Some(action) => perform_action(&mut gs.ecs, action),
+            None => {
+                gs.ecs.write_resource::<Playback>().commands.clear();
+                gs.ecs.fetch_mut::<GameLog>().entries.push(
+                    "The replay no longer matches the game, and has been stopped.".to_string(),
+                );
+                return RunState::AwaitingInput;
+            }
+        },
+    };
+    if gs.ecs.fetch::<Playback>().commands.is_empty() {
+        gs.ecs
+            .fetch_mut::<GameLog>()
+            .entries
+            .push("The replay has ended.".to_string());
+    }
+    // Trading happens in a menu, but the replay carries on from the map
+    match state {
+        RunState::ShowVendor { .. } => RunState::AwaitingInput,
+        state => state,
+    }
+}
+
+/// Saves and loads the world in memory, just as the recorded run was saved and loaded from disk.
+fn reload(gs: &mut State) -> RunState {
+    match saveload_system::reload_world(&mut gs.ecs) {
+        Ok(mut world) => {
+            let playback = gs.ecs.remove::<Playback>().expect("Replay has no playback");
+            world.insert(playback);
+            gs.ecs = world;
+            record_reload(&mut gs.ecs);
+        }
+        Err(e) => {
+            gs.ecs.write_resource::<Playback>().commands.clear();
+            gs.ecs
+                .fetch_mut::<GameLog>()
+                .entries
+                .push(format!("The replay could not reload the game: {}.", e));
+        }
+    }
+    RunState::AwaitingInput
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use crate::map::Map;
+    use crate::simulation;
+    use serde_json::Value;
+
+    /// Where a run has got to, in a form two runs can be compared by.
+    fn outcome(ecs: &World) -> Value {
+        serde_json::json!({
+            "run_info": *ecs.fetch::<RunInfo>(),
+            "rng": *ecs.fetch::<rltk::RandomNumberGenerator>(),
+            "position": *ecs.fetch::<Point>(),
+            "depth": ecs.fetch::<Map>().depth,
+        })
+    }
+
+    /// Writes out the run's recording and reads it back, as a replay would be.
+    fn replay_of(ecs: &World) -> ReplayFile {
+        let text = serde_json::to_string(&ReplayFile::new(&ecs.fetch::<Recording>())).unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn recorded_game_replays_identically() {
+        let mut gs = State { ecs: new_world(21) };
+        gs.resolve(RunState::PreRun);
+        simulation::play(&mut gs, &mut Bot::new(), 400);
+        assert!(gs.ecs.fetch::<Recording>().commands.len() >= 400);
+
+        let replayed = play_to_end(replay_of(&gs.ecs));
+        assert_eq!(outcome(&gs.ecs), outcome(&replayed.ecs));
+    }
+
+    #[test]
+    fn replay_survives_a_reload() {
+        let mut gs = State { ecs: new_world(22) };
+        gs.resolve(RunState::PreRun);
+        let mut bot = Bot::new();
+        simulation::play(&mut gs, &mut bot, 200);
+        gs.ecs = saveload_system::reload_world(&mut gs.ecs).unwrap();
+        record_reload(&mut gs.ecs);
+        simulation::play(&mut gs, &mut bot, 400);
+        assert!(
+            gs.ecs
+                .fetch::<Recording>()
+                .commands
+                .contains(&Command::Reload)
+        );
+
+        let replayed = play_to_end(replay_of(&gs.ecs));
+        assert_eq!(outcome(&gs.ecs), outcome(&replayed.ecs));
+    }
+}
@@ -0,0 +1,56 @@
+//! Run-length encoding for the map's per-tile flags, which are mostly long runs of the same
+//! value. A vector is stored as alternating run lengths, starting with a run of `false`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn encode(bits: &[bool]) -> Vec<u32> {
+    let mut runs = Vec::new();
+    let mut current = false;
+    let mut length = 0;
+    for bit in bits.iter() {
+        if *bit == current {
+            length += 1;
+        } else {
+            runs.push(length);
+            current = *bit;
+            length = 1;
+        }
+    }
+    runs.push(length);
+    runs
+}
+
+pub fn decode(runs: &[u32]) -> Vec<bool> {
+    let mut bits = Vec::new();
+    for (i, run) in runs.iter().enumerate() {
+        bits.extend(std::iter::repeat_n(i % 2 == 1, *run as usize));
+    }
+    bits
+}
+
+pub fn serialize<S: Serializer>(bits: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+    encode(bits).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+    Ok(decode(&Vec::<u32>::deserialize(deserializer)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_alternate_starting_with_false() {
+        assert_eq!(encode(&[true, true, false]), vec![0, 2, 1]);
+        assert_eq!(encode(&[false, false, false]), vec![3]);
+        assert_eq!(encode(&[]), vec![0]);
+    }
+
+    #[test]
+    fn decoding_reverses_encoding() {
+        let bits: Vec<bool> = (0..200).map(|i| i % 7 < 3 || i > 150).collect();
+        assert_eq!(decode(&encode(&bits)), bits);
+        assert!(decode(&encode(&[])).is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+/// What a character is called until the player names them.
+pub const DEFAULT_CHARACTER: &str = "Player";
+
+/// Bookkeeping for the current run: the seed its world was generated from, what the player
+/// named their character, how many turns they've taken so far, and a few statistics for the
+/// end-of-run summary.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct RunInfo {
+    pub seed: u64,
+    pub character: String,
+    pub turns: i32,
+    pub items_used: i32,
+    pub damage_dealt: i32,
+    /// Whatever last hurt the player; once they are dead, this is what killed them.
+    pub last_damaged_by: Option<String>,
+    /// Whether the wizard console has changed anything in the run, which keeps it out of the
+    /// high-score table.
+    pub wizardry: bool,
+}
+
+impl RunInfo {
+    pub fn random_seed() -> u64 {
+        RandomNumberGenerator::new().next_u64()
+    }
+
+    pub fn seeded_run(seed: u64) -> (RunInfo, RandomNumberGenerator) {
+        (
+            RunInfo {
+                seed,
+                character: DEFAULT_CHARACTER.to_string(),
+                ..Default::default()
+            },
+            RandomNumberGenerator::seeded(seed),
+        )
+    }
+}
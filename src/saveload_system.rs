@@ -1,115 +1,789 @@
+use super::RunState;
 use super::components::*;
+use super::gamelog::GameLog;
+use super::replay::Recording;
+use super::run_info::{DEFAULT_CHARACTER, RunInfo};
+use chrono::{Local, TimeZone};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use rltk::{RGB, RandomNumberGenerator};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use specs::prelude::*;
 use specs::saveload::{
-    DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+    DeserializeComponents, MarkedBuilder, Marker, SerializeComponents, SimpleMarker,
+    SimpleMarkerAllocator,
 };
 use std::convert::Infallible;
+use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const SAVE_SLOTS: usize = 5;
+
+/// The run is autosaved to an extra slot after the manual ones, on entering a new depth and
+/// every `AUTOSAVE_INTERVAL` turns. Like any other save, it is consumed when loaded.
+pub const AUTOSAVE_SLOT: usize = SAVE_SLOTS;
+pub const AUTOSAVE_INTERVAL: i32 = 100;
+
+/// The save format written by this build. Bump it (and add a migration) whenever the
+/// layout of a save changes.
+pub const SAVE_VERSION: u32 = 10;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Corrupt(serde_json::Error),
+    CorruptCompact(rmp_serde::decode::Error),
+    Encode(rmp_serde::encode::Error),
+    UnsupportedVersion(u32),
+    Truncated,
+    MissingMap,
+    MissingPlayer,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "the save file could not be accessed ({})", e),
+            SaveError::Corrupt(e) => write!(f, "the save file is damaged ({})", e),
+            SaveError::CorruptCompact(e) => write!(f, "the save file is damaged ({})", e),
+            SaveError::Encode(e) => write!(f, "the game could not be encoded ({})", e),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "the save was made by a newer version of RogueSpace (format {})",
+                version
+            ),
+            SaveError::Truncated => write!(f, "the save file ends unexpectedly"),
+            SaveError::MissingMap => write!(f, "the save file does not contain a map"),
+            SaveError::MissingPlayer => write!(f, "the save file does not contain a player"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Corrupt(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for SaveError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        SaveError::CorruptCompact(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for SaveError {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        SaveError::Encode(e)
+    }
+}
+
+/// How a save is encoded on disk, which is recognised from the file's extension.
+#[derive(PartialEq, Copy, Clone)]
+pub enum SaveFormat {
+    /// A stream of JSON values: large, but readable when debugging.
+    Json,
+    /// A gzip-compressed MessagePack header followed by an array of the component values.
+    /// MessagePack is self-describing, so migrations can work on either format.
+    Compact,
+}
+
+impl SaveFormat {
+    const ALL: [SaveFormat; 2] = [SaveFormat::Compact, SaveFormat::Json];
+
+    fn extension(self) -> &'static str {
+        match self {
+            SaveFormat::Json => "json",
+            SaveFormat::Compact => "sav",
+        }
+    }
+}
+
+/// New saves are compact unless the game is built with the `json-saves` feature.
+#[cfg(feature = "json-saves")]
+pub const WRITE_FORMAT: SaveFormat = SaveFormat::Json;
+#[cfg(not(feature = "json-saves"))]
+pub const WRITE_FORMAT: SaveFormat = SaveFormat::Compact;
+
+/// A summary of a saved run, written at the start of its save file so the load screen can
+/// describe each slot without reading the whole world back in.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveMetadata {
+    pub character: String,
+    pub depth: i32,
+    pub turns: i32,
+    pub timestamp: i64,
+    pub seed: u64,
+}
+
+impl SaveMetadata {
+    fn new(ecs: &World) -> SaveMetadata {
+        let run_info = ecs.fetch::<RunInfo>();
+        SaveMetadata {
+            character: run_info.character.clone(),
+            depth: ecs.fetch::<super::map::Map>().depth,
+            turns: run_info.turns,
+            timestamp: Local::now().timestamp(),
+            seed: run_info.seed,
+        }
+    }
+
+    pub fn saved_at(&self) -> String {
+        match Local.timestamp_opt(self.timestamp, 0).single() {
+            Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+            None => "unknown time".to_string(),
+        }
+    }
+}
+
+/// The first value in every save file.
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    version: u32,
+    metadata: SaveMetadata,
+}
+
+type Migration = fn(&mut Vec<Value>) -> Result<(), SaveError>;
+
+/// `MIGRATIONS[n]` upgrades a save from format `n + 1` to format `n + 2`. Saves are a header
+/// followed by one value per component type; the load screen runs migrations over the header
+/// alone, so they must cope with the component values being absent.
+const MIGRATIONS: &[Migration] = &[
+    // The earlier migrations only ever rewrite values in place
+    |values| wrap_metadata_in_header(values),
+    |values| run_length_encode_map(values),
+    |values| save_global_state(values),
+    |values| track_run_statistics(values),
+    start_recording,
+    |values| name_looks(values),
+    |values| remember_tiles(values),
+    |values| name_characters(values),
+    |values| note_wizardry(values),
+];
+
+/// Format 1 began with the bare metadata, before saves carried a version number.
+fn wrap_metadata_in_header(values: &mut [Value]) -> Result<(), SaveError> {
+    let metadata = values.first_mut().ok_or(SaveError::Truncated)?.take();
+    values[0] = serde_json::json!({ "version": 2, "metadata": metadata });
+    Ok(())
+}
+
+/// Calls `visit` on every JSON object nested anywhere inside `value`.
+fn visit_objects(value: &mut Value, visit: &mut dyn FnMut(&mut serde_json::Map<String, Value>)) {
+    match value {
+        Value::Object(fields) => {
+            visit(fields);
+            fields.values_mut().for_each(|v| visit_objects(v, visit));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| visit_objects(v, visit)),
+        _ => {}
+    }
+}
+
+/// Format 3 run-length encodes the map's per-tile flags; see `rle`.
+fn run_length_encode_map(values: &mut [Value]) -> Result<(), SaveError> {
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if !fields.contains_key("revealed_tiles") {
+                return;
+            }
+            for key in ["revealed_tiles", "visible_tiles", "blocked"] {
+                if let Some(Value::Array(tiles)) = fields.get(key) {
+                    let bits: Vec<bool> = tiles.iter().map(|t| t == &Value::Bool(true)).collect();
+                    fields.insert(
+                        key.to_string(),
+                        serde_json::json!(super::rle::encode(&bits)),
+                    );
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Format 4 saves the log, random number generator, turn counter and run state with the map.
+/// Older saves get an empty log and a generator reseeded from the run's seed.
+fn save_global_state(values: &mut [Value]) -> Result<(), SaveError> {
+    let metadata = values
+        .first()
+        .and_then(|header| header.get("metadata"))
+        .cloned();
+    let Some(metadata) = metadata else {
+        return Err(SaveError::Truncated);
+    };
+    let seed = metadata.get("seed").and_then(Value::as_u64).unwrap_or(0);
+    let turns = metadata.get("turns").cloned().unwrap_or(Value::from(0));
+    let rng = serde_json::to_value(RandomNumberGenerator::seeded(seed))?;
+
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if fields.contains_key("map") && fields.contains_key("identification") {
+                fields.insert("log".to_string(), serde_json::json!({ "entries": [] }));
+                fields.insert("rng".to_string(), rng.clone());
+                fields.insert(
+                    "run_info".to_string(),
+                    serde_json::json!({ "seed": seed, "turns": turns }),
+                );
+                fields.insert("resume_state".to_string(), serde_json::json!("PreRun"));
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Format 5 keeps running totals for the end-of-run summary; older runs start them from zero.
+fn track_run_statistics(values: &mut [Value]) -> Result<(), SaveError> {
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if let Some(Value::Object(run_info)) = fields.get_mut("run_info") {
+                run_info.insert("items_used".to_string(), Value::from(0));
+                run_info.insert("damage_dealt".to_string(), Value::from(0));
+                run_info.insert("last_damaged_by".to_string(), Value::Null);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Where format 5 kept the particles' lifetimes, which were never saved as particles weren't
+/// marked for saving.
+const PARTICLE_LIFETIMES: usize = 28;
+
+/// Format 6 keeps the run's replay recording, and drops the particle lifetimes now that
+/// particles aren't entities. Older runs can carry on, but can't be replayed.
+fn start_recording(values: &mut Vec<Value>) -> Result<(), SaveError> {
+    if values.len() > PARTICLE_LIFETIMES {
+        values.remove(PARTICLE_LIFETIMES);
+    }
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if let Some(seed) = fields
+                .get("run_info")
+                .and_then(|run_info| run_info.get("seed"))
+                .cloned()
+            {
+                fields.insert(
+                    "recording".to_string(),
+                    serde_json::json!({ "seed": seed, "commands": [], "complete": false }),
+                );
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Where the renderables are kept.
+const RENDERABLES: usize = 2;
+
+/// Format 7 gives each renderable a named look for the theme to draw, in place of a fixed
+/// glyph and colours.
+fn name_looks(values: &mut [Value]) -> Result<(), SaveError> {
+    let Some(renderables) = values.get_mut(RENDERABLES) else {
+        return Ok(());
+    };
+    visit_objects(renderables, &mut |fields| {
+        let Some(glyph) = fields.remove("glyph") else {
+            return;
+        };
+        let fg = fields
+            .remove("fg")
+            .and_then(|fg| serde_json::from_value::<RGB>(fg).ok());
+        fields.remove("bg");
+        let look = legacy_look(glyph.as_u64().unwrap_or(0), fg);
+        fields.insert("look".to_string(), Value::from(look));
+    });
+    Ok(())
+}
+
+/// Format 8 remembers what the player last saw on each tile; older maps start out remembering
+/// nothing.
+fn remember_tiles(values: &mut [Value]) -> Result<(), SaveError> {
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if fields.contains_key("revealed_tiles") && !fields.contains_key("remembered") {
+                fields.insert("remembered".to_string(), serde_json::json!({}));
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Format 9 keeps the name the player gave their character with the run. Older runs keep the
+/// name their save was listed under.
+fn name_characters(values: &mut [Value]) -> Result<(), SaveError> {
+    let character = values
+        .first()
+        .and_then(|header| header.get("metadata"))
+        .and_then(|metadata| metadata.get("character"))
+        .cloned()
+        .unwrap_or(Value::from(DEFAULT_CHARACTER));
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if let Some(Value::Object(run_info)) = fields.get_mut("run_info") {
+                run_info
+                    .entry("character")
+                    .or_insert_with(|| character.clone());
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Format 10 notes whether the wizard console has changed the run. Older runs predate the
+/// console making any mark, so count as untouched.
+fn note_wizardry(values: &mut [Value]) -> Result<(), SaveError> {
+    for value in values.iter_mut().skip(1) {
+        visit_objects(value, &mut |fields| {
+            if let Some(Value::Object(run_info)) = fields.get_mut("run_info") {
+                run_info.insert("wizardry".to_string(), Value::Bool(false));
+            }
+        });
+    }
+    Ok(())
+}
+
+/// The look of whatever format 6 drew with `glyph`. Scrolls all shared a glyph, and were told
+/// apart by colour.
+fn legacy_look(glyph: u64, fg: Option<RGB>) -> &'static str {
+    match glyph {
+        25 => "player",
+        127 => "monster.orc",
+        123 => "monster.goblin",
+        75 => "vendor",
+        669 => "potion.health",
+        673 => "medkit",
+        768 => match fg {
+            Some(fg) if fg == RGB::named(rltk::ORANGE) => "scroll.fireball",
+            Some(fg) if fg == RGB::named(rltk::PINK) => "scroll.confusion",
+            Some(fg) if fg == RGB::named(rltk::WHITE) => "scroll.identify",
+            Some(fg) if fg == RGB::named(rltk::YELLOW) => "scroll.remove_curse",
+            _ => "scroll.magic_missile",
+        },
+        427 => "device.plasma_cutter",
+        429 => "device.stasis_emitter",
+        720 => "power_cell",
+        330 => "weapon.dagger",
+        378 => "weapon.longsword",
+        138 => "shield",
+        187 => "shield.tower",
+        817 => "food.rations",
+        237 => "credits",
+        200 => "container.crate",
+        _ => "unknown",
+    }
+}
+
+fn save_version(header: &Value) -> u32 {
+    header
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |version| version as u32)
+}
+
+/// Brings a save written by any earlier build up to `SAVE_VERSION`.
+fn migrate(values: &mut Vec<Value>) -> Result<SaveHeader, SaveError> {
+    let header = values.first().ok_or(SaveError::Truncated)?;
+    let version = save_version(header);
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        migration(values)?;
+    }
+    Ok(serde_json::from_value(values[0].clone())?)
+}
+
+/// The data directory given on the command line, if any.
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keeps everything the game writes in `dir` instead of the usual data directory. Must be
+/// called before anything is read from there.
+pub fn set_data_dir(dir: PathBuf) {
+    let _ = DATA_DIR.set(dir);
+}
+
+/// Saves, replays and the like live in the per-user data directory, falling back to the
+/// working directory on platforms that don't have one.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR.get() {
+        return dir.clone();
+    }
+    match user_data_dir() {
+        Some(dir) => dir,
+        None => PathBuf::from("."),
+    }
+}
+
+#[cfg(not(test))]
+fn user_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("roguespace"))
+}
+
+/// Tests get an empty data directory of their own, so the settings, keymap and themes they see
+/// are the defaults, and nothing they write lands among the player's files.
+#[cfg(test)]
+fn user_data_dir() -> Option<PathBuf> {
+    static TEST_DIR: std::sync::LazyLock<tempfile::TempDir> = std::sync::LazyLock::new(|| {
+        tempfile::tempdir().expect("Unable to create a data directory for the tests")
+    });
+    Some(TEST_DIR.path().to_path_buf())
+}
+
+fn save_dir() -> PathBuf {
+    data_dir().join("saves")
+}
+
+fn slot_path(slot: usize, format: SaveFormat) -> PathBuf {
+    if slot == AUTOSAVE_SLOT {
+        save_dir().join(format!("autosave.{}", format.extension()))
+    } else {
+        save_dir().join(format!("slot{}.{}", slot + 1, format.extension()))
+    }
+}
+
+pub fn slot_label(slot: usize) -> String {
+    if slot == AUTOSAVE_SLOT {
+        "Autosave:".to_string()
+    } else {
+        format!("{}.", slot + 1)
+    }
+}
+
+/// Finds whichever file holds the save in `slot`, in any format.
+fn find_slot(slot: usize) -> Option<(PathBuf, SaveFormat)> {
+    SaveFormat::ALL
+        .iter()
+        .map(|format| (slot_path(slot, *format), *format))
+        .find(|(path, _format)| path.exists())
+}
+
+/// Writes to a temporary file which is then renamed over the save, so a crash part way
+/// through never leaves a truncated save behind.
+fn write_values(path: &Path, format: SaveFormat, values: &[Value]) -> Result<(), SaveError> {
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    match format {
+        SaveFormat::Json => {
+            for value in values.iter() {
+                serde_json::to_writer(&mut writer, value)?;
+            }
+        }
+        SaveFormat::Compact => {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+            rmp_serde::encode::write(&mut encoder, &values[0])?;
+            rmp_serde::encode::write(&mut encoder, &values[1..])?;
+            encoder.finish()?;
+        }
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Reads a save's values back, stopping after the header if that's all the caller needs.
+fn read_values(
+    path: &Path,
+    format: SaveFormat,
+    header_only: bool,
+) -> Result<Vec<Value>, SaveError> {
+    let reader = BufReader::new(File::open(path)?);
+    match format {
+        SaveFormat::Json => {
+            let mut stream = serde_json::Deserializer::from_reader(reader).into_iter::<Value>();
+            if header_only {
+                Ok(vec![stream.next().ok_or(SaveError::Truncated)??])
+            } else {
+                Ok(stream.collect::<Result<Vec<Value>, _>>()?)
+            }
+        }
+        SaveFormat::Compact => {
+            let mut decoder = GzDecoder::new(reader);
+            let mut values = vec![rmp_serde::decode::from_read::<_, Value>(&mut decoder)?];
+            if !header_only {
+                values.extend(rmp_serde::decode::from_read::<_, Vec<Value>>(&mut decoder)?);
+            }
+            Ok(values)
+        }
+    }
+}
 
 macro_rules! serialize_individually {
-    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
+    ($ecs:expr, $values:expr, $data:expr, $( $type:ty),*) => {
         $(
-        SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
-            &( $ecs.read_storage::<$type>(), ),
-            &$data.0,
-            &$data.1,
-            &mut $ser,
-        )
-        .unwrap();
+        $values.push((
+            stringify!($type),
+            SerializeComponents::<Infallible, SimpleMarker<SerializeMe>>::serialize(
+                &( $ecs.read_storage::<$type>(), ),
+                &$data.0,
+                &$data.1,
+                serde_json::value::Serializer,
+            )?,
+        ));
         )*
     };
 }
 
-pub fn savegame(ecs: &mut World) {
+/// The states a saved game can resume in. Every other `RunState` is either a menu or a step
+/// part way through a turn, neither of which a save is ever taken from.
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum ResumeState {
+    AwaitingInput,
+    PreRun,
+}
+
+impl From<ResumeState> for RunState {
+    fn from(state: ResumeState) -> Self {
+        match state {
+            ResumeState::AwaitingInput => RunState::AwaitingInput,
+            ResumeState::PreRun => RunState::PreRun,
+        }
+    }
+}
+
+pub fn savegame(ecs: &mut World, slot: usize, resume_state: ResumeState) -> Result<(), SaveError> {
+    fs::create_dir_all(save_dir())?;
+    save_to_file(
+        ecs,
+        &slot_path(slot, WRITE_FORMAT),
+        WRITE_FORMAT,
+        resume_state,
+    )?;
+    // Don't leave an older save in the other format shadowing this one
+    for format in SaveFormat::ALL.iter().filter(|f| **f != WRITE_FORMAT) {
+        let stale = slot_path(slot, *format);
+        if stale.exists() {
+            fs::remove_file(stale)?;
+        }
+    }
+    Ok(())
+}
+
+fn save_to_file(
+    ecs: &mut World,
+    path: &Path,
+    format: SaveFormat,
+    resume_state: ResumeState,
+) -> Result<(), SaveError> {
+    let values = save_to_values(ecs, resume_state)?;
+    write_values(path, format, &values)
+}
+
+/// Serializes the world. Saving leaves no trace on the world, not even in the markers handed
+/// out afterwards, so a run plays out the same whether or not it was saved along the way.
+fn save_to_values(ecs: &mut World, resume_state: ResumeState) -> Result<Vec<Value>, SaveError> {
+    let header = SaveHeader {
+        version: SAVE_VERSION,
+        metadata: SaveMetadata::new(ecs),
+    };
+    let allocator_copy = ecs
+        .get_mut::<SimpleMarkerAllocator<SerializeMe>>()
+        .unwrap()
+        .clone();
     let map_copy = ecs.get_mut::<super::map::Map>().unwrap().clone();
+    let identification_copy = ecs
+        .get_mut::<super::identification_system::IdentificationMap>()
+        .unwrap()
+        .clone();
+    let log_copy = ecs.get_mut::<GameLog>().unwrap().clone();
+    let rng_copy = ecs.get_mut::<RandomNumberGenerator>().unwrap().clone();
+    let run_info_copy = ecs.get_mut::<RunInfo>().unwrap().clone();
+    let recording_copy = ecs.get_mut::<Recording>().unwrap().clone();
     let save_helper = ecs
         .create_entity()
-        .with(SerializationHelper { map: map_copy })
+        .with(SerializationHelper {
+            map: map_copy,
+            identification: identification_copy,
+            log: log_copy,
+            rng: rng_copy,
+            run_info: run_info_copy,
+            recording: recording_copy,
+            resume_state,
+        })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
-    {
-        let data = (
-            ecs.entities(),
-            ecs.read_storage::<SimpleMarker<SerializeMe>>(),
-        );
-        let writer = File::create("./savegame.json").unwrap();
-        let mut serializer = serde_json::Serializer::new(writer);
-        serialize_individually!(
-            ecs,
-            serializer,
-            data,
-            Position,
-            Renderable,
-            Player,
-            Viewshed,
-            Monster,
-            Name,
-            BlocksTile,
-            CombatStats,
-            SufferDamage,
-            WantsToMelee,
-            Item,
-            Consumable,
-            Ranged,
-            InflictsDamage,
-            AreaOfEffect,
-            Confusion,
-            ProvidesHealing,
-            InBackpack,
-            WantsToPickupItem,
-            WantsToUseItem,
-            WantsToDropItem,
-            SerializationHelper,
-            Equippable,
-            Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
-            WantsToRemoveItem,
-            ParticleLifetime,
-            HungerClock,
-            ProvidesFood
-        );
-    }
+
+    let values = serialize_world(ecs, header);
     ecs.delete_entity(save_helper).expect("Crash on cleanup");
+    ecs.insert(allocator_copy);
+    values
+}
+
+fn serialize_world(ecs: &World, header: SaveHeader) -> Result<Vec<Value>, SaveError> {
+    let mut values = vec![serde_json::to_value(header)?];
+    values.extend(
+        serialize_components(ecs)?
+            .into_iter()
+            .map(|(_name, value)| value),
+    );
+    Ok(values)
+}
+
+/// Every saved component storage, by component name.
+fn serialize_components(ecs: &World) -> Result<Vec<(&'static str, Value)>, SaveError> {
+    let mut values = Vec::new();
+    let data = (
+        ecs.entities(),
+        ecs.read_storage::<SimpleMarker<SerializeMe>>(),
+    );
+    serialize_individually!(
+        ecs,
+        values,
+        data,
+        Position,
+        Renderable,
+        Player,
+        Viewshed,
+        Monster,
+        Name,
+        BlocksTile,
+        CombatStats,
+        SufferDamage,
+        WantsToMelee,
+        Item,
+        Consumable,
+        Ranged,
+        InflictsDamage,
+        AreaOfEffect,
+        Confusion,
+        ProvidesHealing,
+        InBackpack,
+        WantsToPickupItem,
+        WantsToUseItem,
+        WantsToDropItem,
+        SerializationHelper,
+        Equippable,
+        Equipped,
+        MeleePowerBonus,
+        DefenseBonus,
+        WantsToRemoveItem,
+        HungerClock,
+        ProvidesFood,
+        ObfuscatedName,
+        IdentifiedItem,
+        ProvidesIdentification,
+        CursedItem,
+        ProvidesRemoveCurse,
+        MagicItem,
+        Faction,
+        LifeSteal,
+        FactionSlayer,
+        Regeneration,
+        ViewRangeBonus,
+        SlowsHunger,
+        Charges,
+        ProvidesRecharge,
+        Burdened,
+        Wallet,
+        Currency,
+        Vendor,
+        LootContainer,
+        WantsToOpenContainer
+    );
+    Ok(values)
+}
+
+/// Each of `entity`'s saved components, as the JSON it's saved as.
+pub fn dump_entity(ecs: &World, entity: Entity) -> Result<Vec<String>, SaveError> {
+    let Some(marker) = ecs
+        .read_storage::<SimpleMarker<SerializeMe>>()
+        .get(entity)
+        .map(|marker| marker.id())
+    else {
+        return Ok(vec!["(not saved)".to_string()]);
+    };
+    let mut lines = Vec::new();
+    for (name, value) in serialize_components(ecs)? {
+        let Value::Array(entries) = value else {
+            continue;
+        };
+        for entry in entries {
+            if entry["marker"][0] == marker {
+                lines.push(format!("{}: {}", name, entry["components"][0]));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// Reads just the metadata from the front of a slot's save, or `None` if the slot is empty.
+pub fn read_metadata(slot: usize) -> Result<Option<SaveMetadata>, SaveError> {
+    let Some((path, format)) = find_slot(slot) else {
+        return Ok(None);
+    };
+    let mut header = read_values(&path, format, true)?;
+    Ok(Some(migrate(&mut header)?.metadata))
+}
+
+pub fn save_slots() -> Vec<Result<Option<SaveMetadata>, SaveError>> {
+    (0..=AUTOSAVE_SLOT).map(read_metadata).collect()
+}
+
+pub fn first_used_slot() -> Option<usize> {
+    (0..=AUTOSAVE_SLOT).find(|slot| find_slot(*slot).is_some())
+}
+
+pub fn first_free_slot() -> Option<usize> {
+    (0..SAVE_SLOTS).find(|slot| find_slot(*slot).is_none())
 }
 
 pub fn does_save_exist() -> bool {
-    Path::new("./savegame.json").exists()
+    first_used_slot().is_some()
 }
 
 macro_rules! deserialize_individually {
-    ($ecs:expr, $de:expr, $data:expr, $($type:ty),*) => {
+    ($ecs:expr, $values:expr, $data:expr, $($type:ty),*) => {
         $(
         DeserializeComponents::<Infallible, _>::deserialize(
             &mut ( &mut $ecs.write_storage::<$type>(), ),
             &mut $data.0,
             &mut $data.1,
             &mut $data.2,
-            &mut $de,
-        )
-        .unwrap();
+            $values.next().ok_or(SaveError::Truncated)?,
+        )?;
         )*
     };
 }
 
-pub fn load_game(ecs: &mut World) {
-    {
-        // Delete everything
-        let mut to_delete = Vec::new();
-        for e in ecs.entities().join() {
-            to_delete.push(e);
-        }
-        for del in to_delete.iter() {
-            ecs.delete_entity(*del).expect("Deletion failed");
-        }
-    }
+/// Builds the world saved in `slot`. The current world is left as it is, so a damaged save can
+/// simply be rejected.
+pub fn load_game(slot: usize) -> Result<(World, RunState), SaveError> {
+    let (path, format) = find_slot(slot).ok_or(io::Error::from(io::ErrorKind::NotFound))?;
+    load_from_file(&path, format)
+}
 
-    let data = fs::read_to_string("./savegame.json").unwrap();
-    let mut de = serde_json::Deserializer::from_str(&data);
+fn load_from_file(path: &Path, format: SaveFormat) -> Result<(World, RunState), SaveError> {
+    load_from_values(read_values(path, format, false)?)
+}
+
+/// Saves the world and loads it straight back, without touching the disk.
+pub fn reload_world(ecs: &mut World) -> Result<World, SaveError> {
+    let values = save_to_values(ecs, ResumeState::AwaitingInput)?;
+    Ok(load_from_values(values)?.0)
+}
+
+/// Builds a world from a save's values. Loading into an empty world means the entities are
+/// created in the same order every time the same save is loaded.
+fn load_from_values(mut values: Vec<Value>) -> Result<(World, RunState), SaveError> {
+    migrate(&mut values)?;
+    let mut ecs = super::empty_world();
 
     {
+        let mut values = values.into_iter().skip(1);
         let mut d = (
             &mut ecs.entities(),
             &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
@@ -118,7 +792,7 @@ pub fn load_game(ecs: &mut World) {
 
         deserialize_individually!(
             ecs,
-            de,
+            values,
             d,
             Position,
             Renderable,
@@ -147,37 +821,357 @@ pub fn load_game(ecs: &mut World) {
             MeleePowerBonus,
             DefenseBonus,
             WantsToRemoveItem,
-            ParticleLifetime,
             HungerClock,
-            ProvidesFood
+            ProvidesFood,
+            ObfuscatedName,
+            IdentifiedItem,
+            ProvidesIdentification,
+            CursedItem,
+            ProvidesRemoveCurse,
+            MagicItem,
+            Faction,
+            LifeSteal,
+            FactionSlayer,
+            Regeneration,
+            ViewRangeBonus,
+            SlowsHunger,
+            Charges,
+            ProvidesRecharge,
+            Burdened,
+            Wallet,
+            Currency,
+            Vendor,
+            LootContainer,
+            WantsToOpenContainer
         );
     }
 
-    let mut delete_me: Option<Entity> = None;
-    {
+    let (helper_entity, helper) = {
+        let entities = ecs.entities();
+        let helpers = ecs.read_storage::<SerializationHelper>();
+        (&entities, &helpers)
+            .join()
+            .map(|(e, h)| (e, h.clone()))
+            .next()
+            .ok_or(SaveError::MissingMap)?
+    };
+    let (player_entity, player_pos) = {
         let entities = ecs.entities();
-        let helper = ecs.read_storage::<SerializationHelper>();
-        let player = ecs.read_storage::<Player>();
-        let position = ecs.read_storage::<Position>();
-        for (e, h) in (&entities, &helper).join() {
-            let mut world_map = ecs.write_resource::<super::map::Map>();
-            *world_map = h.map.clone();
-            world_map.tile_content = vec![Vec::new(); super::map::MAP_COUNT];
-            delete_me = Some(e);
-        }
-        for (e, _p, pos) in (&entities, &player, &position).join() {
-            let mut ppos = ecs.write_resource::<rltk::Point>();
-            *ppos = rltk::Point::new(pos.x, pos.y);
-            let mut player_resource = ecs.write_resource::<Entity>();
-            *player_resource = e;
-        }
-    }
-    ecs.delete_entity(delete_me.unwrap())
+        let players = ecs.read_storage::<Player>();
+        let positions = ecs.read_storage::<Position>();
+        (&entities, &players, &positions)
+            .join()
+            .map(|(e, _p, pos)| (e, rltk::Point::new(pos.x, pos.y)))
+            .next()
+            .ok_or(SaveError::MissingPlayer)?
+    };
+    ecs.delete_entity(helper_entity)
         .expect("Unable to delete helper");
+
+    let mut world_map = helper.map;
+    world_map.tile_content = vec![Vec::new(); world_map.tiles.len()];
+    ecs.insert(world_map);
+    ecs.insert(helper.identification);
+    ecs.insert(helper.log);
+    ecs.insert(helper.rng);
+    ecs.insert(helper.run_info);
+    ecs.insert(helper.recording);
+    ecs.insert(player_entity);
+    ecs.insert(player_pos);
+    let resume_state: RunState = helper.resume_state.into();
+    ecs.insert(resume_state);
+    Ok((ecs, resume_state))
+}
+
+pub fn delete_save(slot: usize) -> Result<(), SaveError> {
+    if let Some((path, _format)) = find_slot(slot) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
 }
 
-pub fn delete_save() {
-    if Path::new("./savegame.json").exists() {
-        fs::remove_file("./savegame.json").expect("Unable to delete savegame.json");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identification_system::IdentificationMap;
+    use crate::map::Map;
+    use crate::settings::AutoPickup;
+    use crate::tile_memory_system::TileMemorySystem;
+    use crate::wizard::WizardPowers;
+    use crate::{State, new_world, player};
+
+    /// Wanders the player around for `turns` turns, playing each through as the game would.
+    /// The player can't die, so every turn is played.
+    fn play_turns(gs: &mut State, turns: i32) {
+        gs.ecs.write_resource::<WizardPowers>().god_mode = true;
+        let mut rng = RandomNumberGenerator::seeded(turns as u64);
+        for _ in 0..turns {
+            player::try_move_player(
+                rng.range(-1, 2),
+                rng.range(-1, 2),
+                AutoPickup::Off,
+                &mut gs.ecs,
+            );
+            gs.resolve(RunState::PlayerTurn);
+        }
+    }
+
+    /// Whether the map already remembers everything in view, as it should once every step.
+    fn memory_is_up_to_date(ecs: &World) -> bool {
+        let remembered = ecs.fetch::<Map>().remembered.clone();
+        TileMemorySystem {}.run_now(ecs);
+        ecs.fetch::<Map>().remembered == remembered
+    }
+
+    /// Sorts an array that was serialized from an unordered collection.
+    fn sort_array(value: &mut Value) {
+        if let Value::Array(items) = value {
+            items.sort_by_key(|item| item.to_string());
+        }
+    }
+
+    /// Everything a save should preserve, in a form two worlds can be compared by. Entity ids
+    /// differ after a load, so components are ordered by their save marker instead.
+    fn snapshot(ecs: &World) -> Vec<Value> {
+        let header = SaveHeader {
+            version: SAVE_VERSION,
+            metadata: SaveMetadata::new(ecs),
+        };
+        let mut values = serialize_world(ecs, header).unwrap();
+        values.remove(0);
+        values.iter_mut().for_each(sort_array);
+
+        let mut map = serde_json::to_value(&*ecs.fetch::<Map>()).unwrap();
+        sort_array(&mut map["bloodstains"]);
+        let mut identification = serde_json::to_value(&*ecs.fetch::<IdentificationMap>()).unwrap();
+        sort_array(&mut identification["identified_items"]);
+        let player = *ecs.fetch::<Entity>();
+        let player_marker = ecs
+            .read_storage::<SimpleMarker<SerializeMe>>()
+            .get(player)
+            .map(|marker| serde_json::to_value(marker).unwrap());
+
+        values.extend([
+            map,
+            identification,
+            serde_json::to_value(&*ecs.fetch::<GameLog>()).unwrap(),
+            serde_json::to_value(&*ecs.fetch::<RandomNumberGenerator>()).unwrap(),
+            serde_json::to_value(&*ecs.fetch::<RunInfo>()).unwrap(),
+            serde_json::to_value(*ecs.fetch::<rltk::Point>()).unwrap(),
+            serde_json::to_value(player_marker).unwrap(),
+        ]);
+        values
+    }
+
+    /// Saves `gs` to a scratch file and loads it back.
+    fn round_trip(gs: &mut State, format: SaveFormat) -> State {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save");
+        save_to_file(&mut gs.ecs, &path, format, ResumeState::AwaitingInput).unwrap();
+
+        let (ecs, resume) = load_from_file(&path, format).unwrap();
+        assert!(matches!(resume, RunState::AwaitingInput));
+        State { ecs }
+    }
+
+    #[test]
+    fn new_worlds_are_deterministic() {
+        assert_eq!(snapshot(&new_world(7)), snapshot(&new_world(7)));
+        assert_ne!(snapshot(&new_world(7)), snapshot(&new_world(8)));
+    }
+
+    #[test]
+    fn fresh_world_survives_a_round_trip() {
+        for format in SaveFormat::ALL {
+            let mut gs = State { ecs: new_world(1) };
+            let loaded = round_trip(&mut gs, format);
+            assert_eq!(snapshot(&gs.ecs), snapshot(&loaded.ecs));
+        }
+    }
+
+    #[test]
+    fn played_world_survives_a_round_trip() {
+        for format in SaveFormat::ALL {
+            let mut gs = State { ecs: new_world(2) };
+            play_turns(&mut gs, 40);
+            gs.goto_next_level();
+            play_turns(&mut gs, 40);
+            gs.goto_next_level();
+            play_turns(&mut gs, 40);
+
+            let loaded = round_trip(&mut gs, format);
+            assert_eq!(snapshot(&gs.ecs), snapshot(&loaded.ecs));
+            assert_eq!(loaded.ecs.fetch::<Map>().depth, 3);
+            assert_eq!(loaded.ecs.fetch::<RunInfo>().turns, 120);
+        }
+    }
+
+    #[test]
+    fn remembered_tiles_survive_a_round_trip() {
+        for format in SaveFormat::ALL {
+            let mut gs = State { ecs: new_world(5) };
+            // Something in view from the start is remembered before the first turn
+            let player_pos = *gs.ecs.fetch::<rltk::Point>();
+            {
+                let items = gs.ecs.read_storage::<Item>();
+                let mut positions = gs.ecs.write_storage::<Position>();
+                let (_item, pos) = (&items, &mut positions).join().next().unwrap();
+                *pos = Position {
+                    x: player_pos.x,
+                    y: player_pos.y,
+                };
+            }
+            gs.resolve(RunState::PreRun);
+            let idx = gs.ecs.fetch::<Map>().xy_idx(player_pos.x, player_pos.y);
+            assert!(gs.ecs.fetch::<Map>().remembered.contains_key(&idx));
+            play_turns(&mut gs, 20);
+            gs.resolve(RunState::NextLevel);
+            assert!(memory_is_up_to_date(&gs.ecs));
+
+            gs.ecs.fetch_mut::<Map>().visible_tiles.fill(true);
+            TileMemorySystem {}.run_now(&gs.ecs);
+            assert!(gs.ecs.fetch::<Map>().remembered.len() > 1);
+            let loaded = round_trip(&mut gs, format);
+            assert_eq!(
+                loaded.ecs.fetch::<Map>().remembered,
+                gs.ecs.fetch::<Map>().remembered
+            );
+            assert!(memory_is_up_to_date(&loaded.ecs));
+        }
+    }
+
+    #[test]
+    fn loaded_game_rolls_the_same_dice() {
+        let mut gs = State { ecs: new_world(3) };
+        play_turns(&mut gs, 30);
+        let loaded = round_trip(&mut gs, WRITE_FORMAT);
+
+        let mut original = gs.ecs.write_resource::<RandomNumberGenerator>();
+        let mut restored = loaded.ecs.write_resource::<RandomNumberGenerator>();
+        for _ in 0..20 {
+            assert_eq!(original.roll_dice(1, 100), restored.roll_dice(1, 100));
+        }
+    }
+
+    #[test]
+    fn damaged_saves_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save");
+        fs::write(&path, b"not a save").unwrap();
+
+        for format in SaveFormat::ALL {
+            assert!(load_from_file(&path, format).is_err());
+        }
+    }
+
+    #[test]
+    fn saving_leaves_no_trace() {
+        let mark_new_entity = |ecs: &mut World| {
+            let entity = ecs
+                .create_entity()
+                .marked::<SimpleMarker<SerializeMe>>()
+                .build();
+            ecs.read_storage::<SimpleMarker<SerializeMe>>()
+                .get(entity)
+                .map(|marker| serde_json::to_value(marker).unwrap())
+        };
+        let mut saved = new_world(4);
+        let mut unsaved = new_world(4);
+        save_to_values(&mut saved, ResumeState::AwaitingInput).unwrap();
+        assert_eq!(snapshot(&saved), snapshot(&unsaved));
+        assert_eq!(mark_new_entity(&mut saved), mark_new_entity(&mut unsaved));
+    }
+
+    #[test]
+    fn unversioned_header_is_migrated() {
+        let mut values = vec![serde_json::json!({
+            "character": "Player",
+            "depth": 2,
+            "turns": 15,
+            "timestamp": 0,
+            "seed": 9
+        })];
+        let header = migrate(&mut values).unwrap();
+        assert_eq!(header.version, 2);
+        assert_eq!(header.metadata.depth, 2);
+        assert_eq!(header.metadata.seed, 9);
+    }
+
+    #[test]
+    fn renderables_are_given_looks() {
+        let renderable = |glyph: u64, fg: (u8, u8, u8)| {
+            serde_json::json!({
+                "marker": 1,
+                "components": [{
+                    "glyph": glyph,
+                    "fg": serde_json::to_value(RGB::named(fg)).unwrap(),
+                    "bg": serde_json::to_value(RGB::named(rltk::BLACK)).unwrap(),
+                    "render_order": 2
+                }]
+            })
+        };
+        let mut values = vec![
+            serde_json::json!({ "version": 6 }),
+            serde_json::json!([]),
+            serde_json::json!([
+                renderable(25, rltk::YELLOW),
+                renderable(768, rltk::ORANGE),
+                renderable(768, rltk::CYAN)
+            ]),
+        ];
+        name_looks(&mut values).unwrap();
+        let looks: Vec<&Value> = values[RENDERABLES]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entity| &entity["components"][0]["look"])
+            .collect();
+        assert_eq!(looks, ["player", "scroll.fireball", "scroll.magic_missile"]);
+        assert!(
+            values[RENDERABLES][0]["components"][0]
+                .get("glyph")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn old_maps_remember_nothing() {
+        let mut values = vec![
+            serde_json::json!({ "version": 7 }),
+            serde_json::json!([{
+                "marker": [1],
+                "components": [{ "map": { "revealed_tiles": [], "depth": 3 } }]
+            }]),
+        ];
+        remember_tiles(&mut values).unwrap();
+        assert_eq!(
+            values[1][0]["components"][0]["map"]["remembered"],
+            serde_json::json!({})
+        );
+    }
+
+    #[test]
+    fn characters_keep_the_name_they_were_saved_under() {
+        let mut values = vec![
+            serde_json::json!({ "version": 8, "metadata": { "character": "Player" } }),
+            serde_json::json!([{
+                "marker": [1],
+                "components": [{ "run_info": { "seed": 4, "turns": 10 } }]
+            }]),
+        ];
+        name_characters(&mut values).unwrap();
+        assert_eq!(
+            values[1][0]["components"][0]["run_info"]["character"],
+            "Player"
+        );
+    }
+
+    #[test]
+    fn newer_saves_are_rejected() {
+        let mut values = vec![serde_json::json!({ "version": SAVE_VERSION + 1 })];
+        assert!(matches!(
+            migrate(&mut values),
+            Err(SaveError::UnsupportedVersion(v)) if v == SAVE_VERSION + 1
+        ));
     }
 }
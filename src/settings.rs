@@ -0,0 +1,214 @@
+//! The player's preferences, chosen on the Options screen and kept in `settings.json` in the
+//! data directory. Nothing in here may change how a game plays out, since replays don't record
+//! it; anything that does, such as auto-pickup, is read when the player acts and carried in the
+//! action.
+
+use super::{saveload_system, theme};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+
+pub const FPS_CAPS: &[u32] = &[30, 60, 120];
+pub const TILE_SIZES: &[u32] = &[12, 16, 20, 24];
+pub const VERBOSITIES: &[Verbosity] = &[Verbosity::Full, Verbosity::Brief];
+pub const AUTO_PICKUPS: &[AutoPickup] = &[
+    AutoPickup::Off,
+    AutoPickup::Credits,
+    AutoPickup::Consumables,
+    AutoPickup::Everything,
+];
+
+/// How much goes in the message log.
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug, Default)]
+pub enum Verbosity {
+    #[default]
+    Full,
+    /// Leaves out messages that don't need acting on, such as misses and wearing-off effects.
+    Brief,
+}
+
+impl Verbosity {
+    /// How the choice is shown on the Options screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            Verbosity::Full => "Full",
+            Verbosity::Brief => "Brief",
+        }
+    }
+}
+
+/// What the player picks up just by walking onto it.
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug, Default)]
+pub enum AutoPickup {
+    #[default]
+    Off,
+    Credits,
+    /// Credits and anything that's used up.
+    Consumables,
+    Everything,
+}
+
+impl AutoPickup {
+    /// How the choice is shown on the Options screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            AutoPickup::Off => "Off",
+            AutoPickup::Credits => "Credits",
+            AutoPickup::Consumables => "Credits and consumables",
+            AutoPickup::Everything => "Everything",
+        }
+    }
+
+    pub fn picks_up(self, credits: bool, consumable: bool) -> bool {
+        match self {
+            AutoPickup::Off => false,
+            AutoPickup::Credits => credits,
+            AutoPickup::Consumables => credits || consumable,
+            AutoPickup::Everything => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub scanlines: bool,
+    /// Only read at startup.
+    pub fps_cap: u32,
+    /// The size of a tile on screen, in pixels. Only read at startup.
+    pub tile_size: u32,
+    /// The theme's name, or `None` for the default for how the game was built.
+    pub theme: Option<String>,
+    pub palette: String,
+    pub cues: bool,
+    /// Whether to draw the minimap in the corner of the map.
+    pub minimap: bool,
+    pub messages: Verbosity,
+    pub auto_pickup: AutoPickup,
+    /// Whether to ask before doing anything that can't be undone, such as descending.
+    pub confirmations: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            scanlines: true,
+            fps_cap: 30,
+            tile_size: 16,
+            theme: None,
+            palette: "Standard".to_string(),
+            cues: false,
+            minimap: false,
+            messages: Verbosity::Full,
+            auto_pickup: AutoPickup::Off,
+            confirmations: true,
+        }
+    }
+}
+
+fn settings_path() -> PathBuf {
+    saveload_system::data_dir().join("settings.json")
+}
+
+/// Reads the settings file. A missing file gives the defaults, as does an unreadable one, which
+/// is reported.
+fn load() -> Settings {
+    let path = settings_path();
+    let Ok(json) = fs::read_to_string(&path) else {
+        return Settings::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("Unable to read the settings in {}: {}", path.display(), e);
+        Settings::default()
+    })
+}
+
+fn save(settings: &Settings) -> std::io::Result<()> {
+    let path = settings_path();
+    fs::create_dir_all(saveload_system::data_dir())?;
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(settings)?)?;
+    fs::rename(temp_path, path)
+}
+
+static SETTINGS: LazyLock<RwLock<Arc<Settings>>> = LazyLock::new(|| RwLock::new(Arc::new(load())));
+
+pub fn current() -> Arc<Settings> {
+    SETTINGS
+        .read()
+        .expect("Unable to read the settings")
+        .clone()
+}
+
+/// Changes the settings, saves them and redraws the theme to match.
+pub fn update(change: impl FnOnce(&mut Settings)) -> Arc<Settings> {
+    let settings = {
+        let mut settings = SETTINGS.write().expect("Unable to change the settings");
+        let mut changed = Settings::clone(&settings);
+        change(&mut changed);
+        *settings = Arc::new(changed);
+        settings.clone()
+    };
+    theme::select(settings.theme.as_deref(), &settings.palette, settings.cues);
+    if let Err(e) = save(&settings) {
+        eprintln!("Unable to save the settings: {}", e);
+    }
+    settings
+}
+
+/// The choice after `current` in `choices`, or before it, wrapping around. Anything not in
+/// the list is followed by the first choice.
+pub fn cycle<T: PartialEq + Clone>(choices: &[T], current: &T, forward: bool) -> T {
+    let len = choices.len();
+    let index = match choices.iter().position(|choice| choice == current) {
+        Some(i) if forward => (i + 1) % len,
+        Some(i) => (i + len - 1) % len,
+        None => 0,
+    };
+    choices[index].clone()
+}
+
+/// Switches to the next theme, or the previous one.
+pub fn cycle_theme(forward: bool) -> Arc<theme::Theme> {
+    let next = cycle(&theme::theme_names(), &theme::current().name, forward);
+    update(|settings| settings.theme = Some(next));
+    theme::current()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_settings_take_defaults() {
+        let settings: Settings =
+            serde_json::from_str(r#"{ "fps_cap": 60, "auto_pickup": "Credits" }"#).unwrap();
+        assert_eq!(settings.fps_cap, 60);
+        assert_eq!(settings.auto_pickup, AutoPickup::Credits);
+        assert!(settings.scanlines && settings.confirmations);
+        assert_eq!(settings.palette, "Standard");
+
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(serde_json::from_str::<Settings>(&json).unwrap(), settings);
+    }
+
+    #[test]
+    fn tests_never_read_the_players_settings() {
+        assert!(!settings_path().exists());
+        assert_eq!(*current(), Settings::default());
+    }
+
+    #[test]
+    fn choices_cycle_both_ways() {
+        assert_eq!(cycle(FPS_CAPS, &30, true), 60);
+        assert_eq!(cycle(FPS_CAPS, &30, false), 120);
+        assert_eq!(cycle(FPS_CAPS, &45, true), 30);
+        assert_eq!(
+            cycle(AUTO_PICKUPS, &AutoPickup::Everything, true),
+            AutoPickup::Off
+        );
+        assert!(AutoPickup::Consumables.picks_up(false, true));
+        assert!(!AutoPickup::Credits.picks_up(false, true));
+    }
+}
@@ -1,17 +1,33 @@
 use super::{
-    AreaOfEffect, BlocksTile, CombatStats, Confusion, Consumable, DefenseBonus, EquipmentSlot,
-    Equippable, HungerClock, HungerState, InflictsDamage, Item, MAP_WIDTH, Map, MeleePowerBonus,
-    Monster, Name, Player, Position, ProvidesFood, ProvidesHealing, Ranged, Rect, Renderable,
-    SerializeMe, TileType, Viewshed,
+    AreaOfEffect, BlocksTile, Charges, CombatStats, Confusion, Consumable, Currency, CursedItem,
+    DefenseBonus, EquipmentSlot, Equippable, Faction, HungerClock, HungerState, InflictsDamage,
+    Item, LootContainer, Map, MeleePowerBonus, Monster, Name, ObfuscatedName, Player, Position,
+    ProvidesFood, ProvidesHealing, ProvidesIdentification, ProvidesRecharge, ProvidesRemoveCurse,
+    Ranged, Rect, Renderable, SerializeMe, TileType, Vendor, Viewshed, Wallet,
 };
+use crate::identification_system::IdentificationMap;
+use crate::loot_generator::{LootRoll, rarity_multiplier, roll_loot};
 use crate::random_table::RandomTable;
-use rltk::{RGB, RandomNumberGenerator};
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 use specs::saveload::{MarkedBuilder, SimpleMarker};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 const MAX_MONSTERS: i32 = 4;
 
+/// Scrolls and potions are spawned under a per-run alias until the player identifies them.
+pub const SCROLL_NAMES: &[&str] = &[
+    "Magic Missile Scroll",
+    "Fireball Scroll",
+    "Confusion Scroll",
+    "Identify Scroll",
+    "Remove Curse Scroll",
+];
+pub const POTION_NAMES: &[&str] = &["Health Potion"];
+
+/// Every faction a monster can belong to, used when rolling slayer affixes.
+pub const FACTIONS: &[&str] = &["Orc", "Goblin"];
+
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
     ecs.create_entity()
         .with(Position {
@@ -19,9 +35,7 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             y: player_y,
         })
         .with(Renderable {
-            glyph: 25,
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
+            look: "player".to_string(),
             render_order: 0,
         })
         .with(Player {})
@@ -43,31 +57,30 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             state: HungerState::WellFed,
             duration: 20,
         })
+        .with(Wallet { credits: 0 })
         .marked::<SimpleMarker<SerializeMe>>()
         .build()
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, 127, RGB::named(rltk::GREEN2), "Orc");
+fn orc(ecs: &mut World, x: i32, y: i32) -> Entity {
+    monster(ecs, x, y, "monster.orc", "Orc", "Orc")
 }
-fn goblin(ecs: &mut World, x: i32, y: i32) {
-    monster(ecs, x, y, 123, RGB::named(rltk::GREEN2), "Goblin");
+fn goblin(ecs: &mut World, x: i32, y: i32) -> Entity {
+    monster(ecs, x, y, "monster.goblin", "Goblin", "Goblin")
 }
 
 fn monster<S: ToString>(
     ecs: &mut World,
     x: i32,
     y: i32,
-    glyph: rltk::FontCharType,
-    fg: RGB,
+    look: &str,
     name: S,
-) {
+    faction: &str,
+) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph,
-            fg,
-            bg: RGB::named(rltk::BLACK),
+            look: look.to_string(),
             render_order: 1,
         })
         .with(Viewshed {
@@ -79,6 +92,9 @@ fn monster<S: ToString>(
         .with(Name {
             name: name.to_string(),
         })
+        .with(Faction {
+            name: faction.to_string(),
+        })
         .with(BlocksTile {})
         .with(CombatStats {
             max_hp: 16,
@@ -87,72 +103,229 @@ fn monster<S: ToString>(
             power: 4,
         })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn obfuscated_name(ecs: &World, name: &str) -> Option<ObfuscatedName> {
+    let identification = ecs.fetch::<IdentificationMap>();
+    identification
+        .obfuscated_name(name)
+        .map(|alias| ObfuscatedName { name: alias })
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
+fn health_potion(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let obfuscated = obfuscated_name(ecs, "Health Potion");
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 669,
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
+            look: "potion.health".to_string(),
             render_order: 2,
         })
         .with(Name {
             name: "Health Potion".to_string(),
         })
-        .with(Item {})
+        .maybe_with(obfuscated)
+        .with(Item {
+            weight: 0.5,
+            base_value: 25,
+        })
         .with(Consumable {})
         .with(ProvidesHealing { heal_amount: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn create_base_scroll<S: ToString>(
-    ecs: &mut World,
+fn create_base_scroll<'a, S: ToString>(
+    ecs: &'a mut World,
     x: i32,
     y: i32,
     name: S,
-    fg: RGB,
-) -> EntityBuilder<'_> {
+    look: &str,
+) -> EntityBuilder<'a> {
+    let obfuscated = obfuscated_name(ecs, &name.to_string());
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 768,
-            fg,
-            bg: RGB::named(rltk::BLACK),
+            look: look.to_string(),
             render_order: 2,
         })
         .with(Name {
             name: name.to_string(),
         })
-        .with(Item {})
+        .maybe_with(obfuscated)
+        .with(Item {
+            weight: 0.1,
+            base_value: 30,
+        })
         .with(Consumable {})
         .with(Ranged { range: 6 })
         .marked::<SimpleMarker<SerializeMe>>()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
-    create_base_scroll(ecs, x, y, "Magic Missile Scroll", RGB::named(rltk::CYAN))
+fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    create_base_scroll(ecs, x, y, "Magic Missile Scroll", "scroll.magic_missile")
         .with(InflictsDamage { damage: 8 })
-        .build();
+        .build()
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
-    create_base_scroll(ecs, x, y, "Fireball Scroll", RGB::named(rltk::ORANGE))
+fn fireball_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    create_base_scroll(ecs, x, y, "Fireball Scroll", "scroll.fireball")
         .with(InflictsDamage { damage: 20 })
         .with(AreaOfEffect { radius: 3 })
-        .build();
+        .build()
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
-    create_base_scroll(ecs, x, y, "Confusion Scroll", RGB::named(rltk::PINK))
+fn confusion_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    create_base_scroll(ecs, x, y, "Confusion Scroll", "scroll.confusion")
         .with(Confusion { turns: 4 })
-        .build();
+        .build()
 }
 
-type EntitySpawner = for<'a> fn(ecs: &'a mut World, x: i32, y: i32);
+fn identify_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let obfuscated = obfuscated_name(ecs, "Identify Scroll");
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "scroll.identify".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Identify Scroll".to_string(),
+        })
+        .maybe_with(obfuscated)
+        .with(Item {
+            weight: 0.1,
+            base_value: 20,
+        })
+        .with(Consumable {})
+        .with(ProvidesIdentification {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn remove_curse_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let obfuscated = obfuscated_name(ecs, "Remove Curse Scroll");
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "scroll.remove_curse".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Remove Curse Scroll".to_string(),
+        })
+        .maybe_with(obfuscated)
+        .with(Item {
+            weight: 0.1,
+            base_value: 40,
+        })
+        .with(Consumable {})
+        .with(ProvidesRemoveCurse {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn create_base_device<'a, S: ToString>(
+    ecs: &'a mut World,
+    x: i32,
+    y: i32,
+    name: S,
+    look: &str,
+    charges: i32,
+) -> EntityBuilder<'a> {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: look.to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(Item {
+            weight: 2.0,
+            base_value: 60,
+        })
+        .with(Charges {
+            current: charges,
+            max: charges,
+        })
+        .with(Ranged { range: 6 })
+        .marked::<SimpleMarker<SerializeMe>>()
+}
+
+fn plasma_cutter(ecs: &mut World, x: i32, y: i32) -> Entity {
+    create_base_device(ecs, x, y, "Plasma Cutter", "device.plasma_cutter", 5)
+        .with(InflictsDamage { damage: 8 })
+        .build()
+}
+
+fn stasis_emitter(ecs: &mut World, x: i32, y: i32) -> Entity {
+    create_base_device(ecs, x, y, "Stasis Emitter", "device.stasis_emitter", 3)
+        .with(Confusion { turns: 4 })
+        .build()
+}
+
+fn power_cell(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "power_cell".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Power Cell".to_string(),
+        })
+        .with(Item {
+            weight: 1.0,
+            base_value: 25,
+        })
+        .with(Consumable {})
+        .with(ProvidesRecharge {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+type EntitySpawner = for<'a> fn(ecs: &'a mut World, x: i32, y: i32) -> Entity;
+
+/// Everything that can be spawned by name.
+const NAMED_SPAWNERS: &[(&str, EntitySpawner)] = &[
+    ("Orc", orc),
+    ("Goblin", goblin),
+    ("Health Potion", health_potion),
+    ("Medkit", medkit),
+    ("Magic Missile Scroll", magic_missile_scroll),
+    ("Fireball Scroll", fireball_scroll),
+    ("Confusion Scroll", confusion_scroll),
+    ("Identify Scroll", identify_scroll),
+    ("Remove Curse Scroll", remove_curse_scroll),
+    ("Plasma Cutter", plasma_cutter),
+    ("Stasis Emitter", stasis_emitter),
+    ("Power Cell", power_cell),
+    ("Supply Crate", supply_crate),
+    ("Dagger", dagger),
+    ("Shield", shield),
+    ("Longsword", longsword),
+    ("Tower Shield", tower_shield),
+    ("Rations", rations),
+];
+
+/// Looks up the spawner for an entity by its base name, as vendors and the wizard console do.
+fn named_spawner(name: &str) -> Option<EntitySpawner> {
+    NAMED_SPAWNERS
+        .iter()
+        .find(|(other, _spawner)| *other == name)
+        .map(|(_name, spawner)| *spawner)
+}
+
+/// The names `spawn_named` knows.
+pub fn spawnable_names() -> impl Iterator<Item = &'static str> {
+    NAMED_SPAWNERS.iter().map(|(name, _spawner)| *name)
+}
+
+pub fn spawn_named(ecs: &mut World, name: &str, x: i32, y: i32) -> Option<Entity> {
+    named_spawner(name).map(|spawner| spawner(ecs, x, y))
+}
 
 fn room_table(map_depth: i32) -> RandomTable<EntitySpawner> {
     RandomTable::<EntitySpawner>::new()
@@ -162,6 +335,13 @@ fn room_table(map_depth: i32) -> RandomTable<EntitySpawner> {
         .add(fireball_scroll, 2 + map_depth)
         .add(confusion_scroll, 2 + map_depth)
         .add(magic_missile_scroll, 4)
+        .add(identify_scroll, 3)
+        .add(remove_curse_scroll, 2)
+        .add(plasma_cutter, map_depth - 1)
+        .add(stasis_emitter, map_depth - 2)
+        .add(power_cell, 2)
+        .add(medkit, 2)
+        .add(supply_crate, 3)
         .add(dagger, 3)
         .add(shield, 3)
         .add(longsword, map_depth - 1)
@@ -171,7 +351,8 @@ fn room_table(map_depth: i32) -> RandomTable<EntitySpawner> {
 
 pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32, map: &Map) {
     let spawn_table = room_table(map_depth);
-    let mut spawn_points: HashMap<usize, Option<EntitySpawner>> = HashMap::new();
+    // Ordered, so that the same seed always spawns the same room
+    let mut spawn_points: BTreeMap<usize, Option<EntitySpawner>> = BTreeMap::new();
     {
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
         let num_spawns = rng.roll_dice(1, MAX_MONSTERS + 3) + (map_depth - 1) - 3;
@@ -181,7 +362,7 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32, map: &Map) {
             while tries < 20 {
                 let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
                 let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAP_WIDTH) + x;
+                let idx = (y * map.width as usize) + x;
 
                 if spawn_points.contains_key(&idx) {
                     // We're already spawning something at this location, so we try again
@@ -203,8 +384,8 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32, map: &Map) {
     }
 
     for (spawn_index, spawner) in spawn_points.iter() {
-        let x = (*spawn_index % MAP_WIDTH) as i32;
-        let y = (*spawn_index / MAP_WIDTH) as i32;
+        let x = *spawn_index as i32 % map.width;
+        let y = *spawn_index as i32 / map.width;
 
         if let Some(spawner) = spawner {
             spawner(ecs, x, y);
@@ -212,105 +393,269 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32, map: &Map) {
     }
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+/// The rolled quality, enchantment and affixes of a piece of spawned equipment.
+struct EquipmentRoll {
+    name: String,
+    obfuscated: ObfuscatedName,
+    bonus: i32,
+    cursed: bool,
+    value: i32,
+    loot: LootRoll,
+}
+
+fn roll_equipment(
+    ecs: &mut World,
+    base_name: &str,
+    base_bonus: i32,
+    base_value: i32,
+    slot: EquipmentSlot,
+) -> EquipmentRoll {
+    let depth = ecs.fetch::<Map>().depth;
+    let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+
+    let (quality, quality_bonus) = match rng.roll_dice(1, 20) + depth {
+        1..=4 => ("Rusty ", -1),
+        5..=16 => ("", 0),
+        17..=22 => ("Fine ", 1),
+        _ => ("Masterwork ", 2),
+    };
+    let enchantment = match rng.roll_dice(1, 20) {
+        1..=2 => -2,
+        3..=5 => -1,
+        6..=15 => 0,
+        16..=19 => 1 + depth / 4,
+        _ => 2 + depth / 3,
+    };
+    let loot = roll_loot(&mut rng, depth, slot);
+    let full_name = loot.name(&format!("{}{}", quality, base_name));
+
+    EquipmentRoll {
+        name: match enchantment {
+            0 => full_name,
+            enchantment => format!("{:+} {}", enchantment, full_name),
+        },
+        obfuscated: ObfuscatedName {
+            name: format!("{}{}", quality, base_name),
+        },
+        bonus: base_bonus + quality_bonus + enchantment,
+        cursed: enchantment < 0,
+        value: i32::max(
+            1,
+            (base_value + (quality_bonus + enchantment) * 10) * rarity_multiplier(loot.class),
+        ),
+        loot,
+    }
+}
+
+fn dagger(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let roll = roll_equipment(ecs, "Dagger", 2, 10, EquipmentSlot::Melee);
+    let builder = ecs
+        .create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 330,
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
+            look: "weapon.dagger".to_string(),
             render_order: 2,
         })
-        .with(Name {
-            name: "Dagger".to_string(),
+        .with(Name { name: roll.name })
+        .with(roll.obfuscated)
+        .with(Item {
+            weight: 1.0,
+            base_value: roll.value,
         })
-        .with(Item {})
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
-        .with(MeleePowerBonus { power: 2 })
+        .with(MeleePowerBonus { power: roll.bonus })
+        .maybe_with(roll.cursed.then_some(CursedItem {}));
+    roll.loot
+        .apply(builder)
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+fn shield(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let roll = roll_equipment(ecs, "Shield", 2, 15, EquipmentSlot::Shield);
+    let builder = ecs
+        .create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 138,
-            fg: RGB::named(rltk::CYAN),
-            bg: RGB::named(rltk::BLACK),
+            look: "shield".to_string(),
             render_order: 2,
         })
-        .with(Name {
-            name: "Shield".to_string(),
+        .with(Name { name: roll.name })
+        .with(roll.obfuscated)
+        .with(Item {
+            weight: 6.0,
+            base_value: roll.value,
         })
-        .with(Item {})
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
-        .with(DefenseBonus { defense: 2 })
+        .with(DefenseBonus {
+            defense: roll.bonus,
+        })
+        .maybe_with(roll.cursed.then_some(CursedItem {}));
+    roll.loot
+        .apply(builder)
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+fn longsword(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let roll = roll_equipment(ecs, "Longsword", 4, 30, EquipmentSlot::Melee);
+    let builder = ecs
+        .create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 378,
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
+            look: "weapon.longsword".to_string(),
             render_order: 2,
         })
-        .with(Name {
-            name: "Longsword".to_string(),
+        .with(Name { name: roll.name })
+        .with(roll.obfuscated)
+        .with(Item {
+            weight: 3.0,
+            base_value: roll.value,
         })
-        .with(Item {})
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
-        .with(MeleePowerBonus { power: 4 })
+        .with(MeleePowerBonus { power: roll.bonus })
+        .maybe_with(roll.cursed.then_some(CursedItem {}));
+    roll.loot
+        .apply(builder)
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
+fn tower_shield(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let roll = roll_equipment(ecs, "Tower Shield", 3, 35, EquipmentSlot::Shield);
+    let builder = ecs
+        .create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 187,
-            fg: RGB::named(rltk::YELLOW),
-            bg: RGB::named(rltk::BLACK),
+            look: "shield.tower".to_string(),
             render_order: 2,
         })
-        .with(Name {
-            name: "Tower Shield".to_string(),
+        .with(Name { name: roll.name })
+        .with(roll.obfuscated)
+        .with(Item {
+            weight: 12.0,
+            base_value: roll.value,
         })
-        .with(Item {})
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
-        .with(DefenseBonus { defense: 3 })
+        .with(DefenseBonus {
+            defense: roll.bonus,
+        })
+        .maybe_with(roll.cursed.then_some(CursedItem {}));
+    roll.loot
+        .apply(builder)
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
+fn rations(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
-            glyph: 817,
-            fg: RGB::named(rltk::SANDYBROWN),
-            bg: RGB::named(rltk::BLACK),
+            look: "food.rations".to_string(),
             render_order: 2,
         })
         .with(Name {
             name: "Rations".to_string(),
         })
-        .with(Item {})
+        .with(Item {
+            weight: 0.5,
+            base_value: 5,
+        })
         .with(ProvidesFood {})
         .with(Consumable {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn medkit(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "medkit".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Medkit".to_string(),
+        })
+        .with(Item {
+            weight: 1.0,
+            base_value: 30,
+        })
+        .with(ProvidesHealing { heal_amount: 15 })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn credit_chip(ecs: &mut World, x: i32, y: i32, amount: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "credits".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("{} Credits", amount),
+        })
+        .with(Item {
+            weight: 0.0,
+            base_value: amount,
+        })
+        .with(Currency { amount })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn supply_crate(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let credits = {
+        let depth = ecs.fetch::<Map>().depth;
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        rng.roll_dice(2, 10) + depth * 5
+    };
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "container.crate".to_string(),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Supply Crate".to_string(),
+        })
+        .with(BlocksTile {})
+        .with(LootContainer { credits })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+pub fn vendor(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            look: "vendor".to_string(),
+            render_order: 1,
+        })
+        .with(Name {
+            name: "Quartermaster".to_string(),
+        })
+        .with(BlocksTile {})
+        .with(Vendor {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Every third level has a quartermaster waiting beside the player's arrival point.
+pub fn spawn_vendor(ecs: &mut World, map: &Map) {
+    if map.depth % 3 != 0 {
+        return;
+    }
+    let (x, y) = map.rooms[0].center();
+    vendor(ecs, x + 1, y);
 }
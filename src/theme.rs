@@ -0,0 +1,442 @@
+//! How everything on the map is drawn. Render code asks for a look by name ("wall.ns", "player",
+//! "potion.health", "particle.hit") and the current theme says which glyph and colour that is.
+//! Themes are read from `resources/themes.json`, plus an optional `themes.json` in the data
+//! directory, and can be switched while playing.
+//!
+//! A look that isn't listed falls back to the name before its last dot, so "scroll.fireball"
+//! is drawn as "scroll" if the theme doesn't say otherwise, and then to "unknown". A theme can
+//! extend another, and anything it leaves out is taken from the one it extends.
+//!
+//! On top of the theme goes a palette, which only changes colours, such as those for players
+//! with colour blindness. Some things are also told apart by more than colour when the
+//! non-colour cues are turned on.
+
+use super::{saveload_system, settings};
+use rltk::{FontCharType, RGB};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, LazyLock, RwLock};
+
+const BUILT_IN_THEMES: &str = include_str!("../resources/themes.json");
+
+/// The theme used when the game starts: characters in the terminal, tiles otherwise.
+const DEFAULT_THEME: &str = if cfg!(feature = "terminal") {
+    "ASCII"
+} else {
+    "Tiles"
+};
+
+/// A glyph and colour to draw something with.
+#[derive(Clone, Copy)]
+pub struct Look {
+    pub glyph: FontCharType,
+    pub fg: RGB,
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    themes: Vec<ThemeData>,
+    #[serde(default)]
+    palettes: Vec<PaletteData>,
+}
+
+#[derive(Deserialize)]
+struct ThemeData {
+    name: String,
+    extends: Option<String>,
+    tileset: Option<bool>,
+    looks: HashMap<String, LookData>,
+}
+
+#[derive(Deserialize)]
+struct PaletteData {
+    name: String,
+    /// Colours by look name, as HTML colour codes.
+    colours: HashMap<String, String>,
+}
+
+/// A glyph is either an index into the tileset or, for themes that don't use it, a character.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GlyphData {
+    Index(FontCharType),
+    Character(char),
+}
+
+#[derive(Deserialize)]
+struct LookData {
+    glyph: Option<GlyphData>,
+    /// An HTML colour code, such as `#FFD700`.
+    fg: Option<String>,
+}
+
+/// A look as one theme gives it, which may leave the glyph or colour to a fallback.
+struct PartialLook {
+    glyph: Option<FontCharType>,
+    fg: Option<RGB>,
+}
+
+impl LookData {
+    fn parse(self, name: &str) -> Result<PartialLook, String> {
+        let glyph = match self.glyph {
+            None => None,
+            Some(GlyphData::Index(index)) => Some(index),
+            Some(GlyphData::Character(c)) => match rltk::to_cp437(c) {
+                0 => {
+                    return Err(format!(
+                        "{} is drawn with {:?}, which can't be shown",
+                        name, c
+                    ));
+                }
+                glyph => Some(glyph),
+            },
+        };
+        let fg = self.fg.map(|code| parse_colour(name, &code)).transpose()?;
+        Ok(PartialLook { glyph, fg })
+    }
+}
+
+fn parse_colour(name: &str, code: &str) -> Result<RGB, String> {
+    RGB::from_hex(code).map_err(|_| format!("{} has an invalid colour {}", name, code))
+}
+
+type Looks = HashMap<String, PartialLook>;
+
+pub struct Theme {
+    pub name: String,
+    /// The palette it's drawn with, if any.
+    pub palette: String,
+    /// Whether glyphs index the tileset rather than code page 437.
+    pub tileset: bool,
+    /// Whether to tell things apart by more than their colour.
+    pub cues: bool,
+    /// The palette's colours, then this theme's own looks, followed by those of each theme it
+    /// extends.
+    layers: Vec<Arc<Looks>>,
+}
+
+pub struct Palette {
+    pub name: String,
+    colours: Arc<Looks>,
+}
+
+impl Palette {
+    fn from_data(data: PaletteData) -> Result<Palette, String> {
+        let mut colours = HashMap::new();
+        for (name, code) in data.colours {
+            let fg = Some(parse_colour(&name, &code)?);
+            colours.insert(name, PartialLook { glyph: None, fg });
+        }
+        Ok(Palette {
+            name: data.name,
+            colours: Arc::new(colours),
+        })
+    }
+}
+
+impl Theme {
+    fn from_data(data: ThemeData, themes: &[Arc<Theme>]) -> Result<Theme, String> {
+        let base = match &data.extends {
+            None => None,
+            Some(name) => {
+                let base = themes.iter().find(|theme| &theme.name == name);
+                Some(base.ok_or(format!(
+                    "{} extends {}, which isn't defined before it",
+                    data.name, name
+                ))?)
+            }
+        };
+        let mut looks = HashMap::new();
+        for (name, look) in data.looks {
+            let look = look.parse(&name)?;
+            looks.insert(name, look);
+        }
+
+        let mut layers = vec![Arc::new(looks)];
+        if let Some(base) = base {
+            layers.extend(base.layers.iter().cloned());
+        }
+        Ok(Theme {
+            name: data.name,
+            palette: String::new(),
+            tileset: data.tileset.unwrap_or(base.is_none_or(|base| base.tileset)),
+            cues: false,
+            layers,
+        })
+    }
+
+    /// This theme as drawn with `palette`.
+    fn with_palette(&self, palette: &Palette, cues: bool) -> Theme {
+        let mut layers = vec![palette.colours.clone()];
+        layers.extend(self.layers.iter().cloned());
+        Theme {
+            name: self.name.clone(),
+            palette: palette.name.clone(),
+            tileset: self.tileset,
+            cues,
+            layers,
+        }
+    }
+
+    /// The glyph and colour to draw `name` with.
+    pub fn look(&self, name: &str) -> Look {
+        let mut glyph = None;
+        let mut fg = None;
+        let mut fill = |look: &PartialLook| {
+            glyph = glyph.or(look.glyph);
+            fg = fg.or(look.fg);
+        };
+
+        for looks in self.layers.iter() {
+            let mut key = name;
+            loop {
+                if let Some(look) = looks.get(key) {
+                    fill(look);
+                }
+                match key.rfind('.') {
+                    Some(dot) => key = &key[..dot],
+                    None => break,
+                }
+            }
+        }
+        for looks in self.layers.iter() {
+            if let Some(look) = looks.get("unknown") {
+                fill(look);
+            }
+        }
+
+        Look {
+            glyph: glyph.unwrap_or(rltk::to_cp437('?')),
+            fg: fg.unwrap_or(RGB::named(rltk::WHITE)),
+        }
+    }
+}
+
+/// Every theme and palette that can be chosen.
+#[derive(Default)]
+struct Catalogue {
+    themes: Vec<Arc<Theme>>,
+    palettes: Vec<Arc<Palette>>,
+}
+
+/// Adds the themes and palettes in `json` to `catalogue`, replacing any with the same name.
+/// Nothing is added if any of them are invalid.
+fn parse_themes(json: &str, catalogue: &mut Catalogue) -> Result<(), String> {
+    let file: ThemeFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut themes = catalogue.themes.clone();
+    for data in file.themes {
+        let theme = Arc::new(Theme::from_data(data, &themes)?);
+        match themes.iter().position(|other| other.name == theme.name) {
+            Some(i) => themes[i] = theme,
+            None => themes.push(theme),
+        }
+    }
+    let mut palettes = catalogue.palettes.clone();
+    for data in file.palettes {
+        let palette = Arc::new(Palette::from_data(data)?);
+        match palettes.iter().position(|other| other.name == palette.name) {
+            Some(i) => palettes[i] = palette,
+            None => palettes.push(palette),
+        }
+    }
+    *catalogue = Catalogue { themes, palettes };
+    Ok(())
+}
+
+struct ThemeRegistry {
+    catalogue: Catalogue,
+    theme: usize,
+    palette: usize,
+    cues: bool,
+    /// The current theme, drawn with the current palette.
+    drawn: Arc<Theme>,
+}
+
+impl ThemeRegistry {
+    fn load() -> ThemeRegistry {
+        let mut catalogue = Catalogue::default();
+        parse_themes(BUILT_IN_THEMES, &mut catalogue).expect("Unable to load the built-in themes");
+
+        let path = saveload_system::data_dir().join("themes.json");
+        if let Ok(json) = fs::read_to_string(&path)
+            && let Err(e) = parse_themes(&json, &mut catalogue)
+        {
+            eprintln!("Unable to load the themes in {}: {}", path.display(), e);
+        }
+
+        let drawn = Arc::new(catalogue.themes[0].with_palette(&catalogue.palettes[0], false));
+        let mut registry = ThemeRegistry {
+            catalogue,
+            theme: 0,
+            palette: 0,
+            cues: false,
+            drawn,
+        };
+        let settings = settings::current();
+        registry.select(settings.theme.as_deref(), &settings.palette, settings.cues);
+        registry.redraw();
+        registry
+    }
+
+    fn select(&mut self, theme: Option<&str>, palette: &str, cues: bool) {
+        let theme = theme.unwrap_or(DEFAULT_THEME);
+        let catalogue = &self.catalogue;
+        self.theme = catalogue
+            .themes
+            .iter()
+            .position(|other| other.name == theme)
+            .or_else(|| {
+                catalogue
+                    .themes
+                    .iter()
+                    .position(|other| other.name == DEFAULT_THEME)
+            })
+            .unwrap_or(0);
+        self.palette = catalogue
+            .palettes
+            .iter()
+            .position(|other| other.name == palette)
+            .unwrap_or(0);
+        self.cues = cues;
+    }
+
+    fn redraw(&mut self) {
+        let theme = &self.catalogue.themes[self.theme];
+        let palette = &self.catalogue.palettes[self.palette];
+        self.drawn = Arc::new(theme.with_palette(palette, self.cues));
+    }
+}
+
+static THEMES: LazyLock<RwLock<ThemeRegistry>> =
+    LazyLock::new(|| RwLock::new(ThemeRegistry::load()));
+
+/// The theme everything is currently drawn with, in the current palette.
+pub fn current() -> Arc<Theme> {
+    THEMES
+        .read()
+        .expect("Unable to read the themes")
+        .drawn
+        .clone()
+}
+
+fn change(change: impl FnOnce(&mut ThemeRegistry)) -> Arc<Theme> {
+    let mut registry = THEMES.write().expect("Unable to change the theme");
+    change(&mut registry);
+    registry.redraw();
+    registry.drawn.clone()
+}
+
+/// Draws with the theme and palette with these names, where they exist, or the defaults
+/// otherwise. `None` is the default theme.
+pub fn select(theme: Option<&str>, palette: &str, cues: bool) -> Arc<Theme> {
+    change(|registry| registry.select(theme, palette, cues))
+}
+
+/// The names of the themes that can be chosen.
+pub fn theme_names() -> Vec<String> {
+    let registry = THEMES.read().expect("Unable to read the themes");
+    registry
+        .catalogue
+        .themes
+        .iter()
+        .map(|theme| theme.name.clone())
+        .collect()
+}
+
+/// The names of the palettes that can be chosen.
+pub fn palette_names() -> Vec<String> {
+    let registry = THEMES.read().expect("Unable to read the themes");
+    registry
+        .catalogue
+        .palettes
+        .iter()
+        .map(|palette| palette.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_in() -> Catalogue {
+        let mut catalogue = Catalogue::default();
+        parse_themes(BUILT_IN_THEMES, &mut catalogue).unwrap();
+        catalogue
+    }
+
+    #[test]
+    fn looks_fall_back_by_name_then_theme() {
+        let themes = built_in().themes;
+        let tiles = &themes[0];
+        let ascii = themes.iter().find(|t| t.name == "ASCII").unwrap();
+
+        assert_eq!(tiles.look("wall.ns").glyph, 855);
+        assert_eq!(tiles.look("wall.ns").fg, RGB::from_hex("#00FF00").unwrap());
+        assert_eq!(ascii.look("wall.ns").glyph, rltk::to_cp437('#'));
+        assert_eq!(
+            ascii.look("scroll.fireball").fg,
+            tiles.look("scroll.fireball").fg
+        );
+        assert_eq!(ascii.look("scroll.fireball").glyph, rltk::to_cp437('?'));
+        assert_eq!(ascii.look("no.such.thing").glyph, rltk::to_cp437('?'));
+        assert!(!ascii.tileset);
+    }
+
+    #[test]
+    fn palettes_only_change_colours() {
+        let catalogue = built_in();
+        assert_eq!(catalogue.palettes[0].name, "Standard");
+        let ascii = catalogue.themes.iter().find(|t| t.name == "ASCII").unwrap();
+        for palette in catalogue.palettes.iter() {
+            let drawn = ascii.with_palette(palette, true);
+            assert_eq!(drawn.look("wall.ns").glyph, rltk::to_cp437('#'));
+            assert_eq!(drawn.look("floor.bloodstain").glyph, rltk::to_cp437(','));
+            assert!(!drawn.tileset && drawn.cues);
+        }
+        let contrast = catalogue
+            .palettes
+            .iter()
+            .find(|p| p.name == "High Contrast")
+            .unwrap();
+        let drawn = ascii.with_palette(contrast, false);
+        assert_eq!(drawn.look("wall.ns").fg, RGB::named(rltk::WHITE));
+        // Every palette should cover the colours that matter most
+        for palette in catalogue.palettes.iter().skip(1) {
+            for name in [
+                "bloodstain",
+                "ui.hunger.starving",
+                "ui.target.invalid",
+                "monster",
+            ] {
+                assert!(
+                    palette.colours.contains_key(name),
+                    "{} in {}",
+                    name,
+                    palette.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_themes_are_rejected() {
+        let mut catalogue = built_in();
+        let count = catalogue.themes.len();
+        let bad_colour =
+            r##"{ "themes": [{ "name": "Bad", "looks": { "floor": { "fg": "green" } } }] }"##;
+        assert!(parse_themes(bad_colour, &mut catalogue).is_err());
+        let bad_base = r#"{ "themes": [{ "name": "Bad", "extends": "Nope", "looks": {} }] }"#;
+        assert!(parse_themes(bad_base, &mut catalogue).is_err());
+        let bad_palette = r##"{ "palettes": [{ "name": "Bad", "colours": { "wall": "#12" } }] }"##;
+        assert!(parse_themes(bad_palette, &mut catalogue).is_err());
+        assert_eq!(catalogue.themes.len(), count);
+
+        let custom =
+            r##"{ "themes": [{ "name": "Tiles", "looks": { "floor": { "glyph": 3 } } }] }"##;
+        parse_themes(custom, &mut catalogue).unwrap();
+        assert_eq!(catalogue.themes.len(), count);
+        assert_eq!(catalogue.themes[0].look("floor").glyph, 3);
+    }
+}
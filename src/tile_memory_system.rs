@@ -0,0 +1,42 @@
+use super::{Map, Monster, Player, Position, Renderable};
+use specs::prelude::*;
+
+/// Notes what's on every tile in view that stays put, such as items, crates and the vendor, so
+/// it can still be drawn once the tile is out of sight.
+pub struct TileMemorySystem {}
+
+impl<'a> System<'a> for TileMemorySystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Player>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, entities, positions, renderables, monsters, players) = data;
+
+        let map = &mut *map;
+        for (idx, visible) in map.visible_tiles.iter().enumerate() {
+            if *visible {
+                map.remembered.remove(&idx);
+            }
+        }
+
+        let mut seen: Vec<(usize, &Renderable)> = (&entities, &positions, &renderables)
+            .join()
+            .filter(|(entity, _pos, _render)| {
+                monsters.get(*entity).is_none() && players.get(*entity).is_none()
+            })
+            .map(|(_entity, pos, render)| (map.xy_idx(pos.x, pos.y), render))
+            .filter(|(idx, _render)| map.visible_tiles[*idx])
+            .collect();
+        // Whatever would be drawn on top is what's remembered
+        seen.sort_by_key(|(_idx, render)| std::cmp::Reverse(render.render_order));
+        for (idx, render) in seen {
+            map.remembered.insert(idx, render.look.clone());
+        }
+    }
+}
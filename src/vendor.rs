@@ -0,0 +1,106 @@
+use super::{
+    CombatStats, Equipped, InBackpack, Item, Name, ObfuscatedName, Position, Wallet,
+    encumbrance_system::{OVERLOAD_MULTIPLIER, carried_weight, carry_capacity},
+    gamelog::GameLog,
+    identification_system::get_item_display_name,
+    spawner,
+};
+use specs::prelude::*;
+
+/// What a quartermaster keeps on their shelves, and what each item costs.
+pub const STOCK: &[(&str, i32)] = &[("Rations", 10), ("Medkit", 45), ("Power Cell", 40)];
+
+/// Vendors pay half of an item's base value, but never less than a single credit.
+pub fn sell_price(item: &Item) -> i32 {
+    i32::max(1, item.base_value / 2)
+}
+
+pub fn player_credits(ecs: &World) -> i32 {
+    let player_entity = ecs.fetch::<Entity>();
+    let wallets = ecs.read_storage::<Wallet>();
+    wallets
+        .get(*player_entity)
+        .map_or(0, |wallet| wallet.credits)
+}
+
+pub fn sell_item(ecs: &mut World, item: Entity) {
+    let price = {
+        let items = ecs.read_storage::<Item>();
+        items.get(item).map_or(0, sell_price)
+    };
+    let name = get_item_display_name(
+        &ecs.read_storage::<Name>(),
+        &ecs.read_storage::<ObfuscatedName>(),
+        item,
+    );
+    {
+        let player_entity = ecs.fetch::<Entity>();
+        let mut wallets = ecs.write_storage::<Wallet>();
+        if let Some(wallet) = wallets.get_mut(*player_entity) {
+            wallet.credits += price;
+        }
+    }
+    ecs.delete_entity(item).expect("Unable to delete sold item");
+
+    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    gamelog
+        .entries
+        .push(format!("You sell the {} for {} credits.", name, price));
+}
+
+/// Whether `owner` could take `item` as well as everything they already carry, by the same
+/// measure as picking it up.
+fn can_carry(ecs: &World, owner: Entity, item: Entity) -> bool {
+    let items = ecs.read_storage::<Item>();
+    let Some(stats) = ecs.read_storage::<CombatStats>().get(owner).cloned() else {
+        return true;
+    };
+    let weight = carried_weight(
+        owner,
+        &items,
+        &ecs.read_storage::<InBackpack>(),
+        &ecs.read_storage::<Equipped>(),
+    );
+    weight + items.get(item).map_or(0.0, |item| item.weight)
+        <= carry_capacity(&stats) * OVERLOAD_MULTIPLIER
+}
+
+pub fn buy_item(ecs: &mut World, name: &str, price: i32) {
+    if player_credits(ecs) < price {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog
+            .entries
+            .push(format!("You cannot afford the {}.", name));
+        return;
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    if let Some(item) = spawner::spawn_named(ecs, name, 0, 0) {
+        if !can_carry(ecs, player_entity, item) {
+            ecs.delete_entity(item)
+                .expect("Unable to delete unsold item");
+            let mut gamelog = ecs.fetch_mut::<GameLog>();
+            gamelog
+                .entries
+                .push(format!("The {} is too heavy for you to carry.", name));
+            return;
+        }
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(
+                item,
+                InBackpack {
+                    owner: player_entity,
+                },
+            )
+            .expect("Unable to insert backpack entry");
+        if let Some(wallet) = ecs.write_storage::<Wallet>().get_mut(player_entity) {
+            wallet.credits -= price;
+        }
+
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog
+            .entries
+            .push(format!("You buy the {} for {} credits.", name, price));
+    }
+}
@@ -1,4 +1,4 @@
-use super::{Map, Player, Position, Viewshed};
+use super::{Equipped, Map, Player, Position, ViewRangeBonus, Viewshed, wizard::WizardPowers};
 use rltk::{Point, field_of_view};
 use specs::prelude::*;
 
@@ -11,16 +11,24 @@ impl<'a> System<'a> for VisibilitySystem {
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Player>,
+        ReadStorage<'a, ViewRangeBonus>,
+        ReadStorage<'a, Equipped>,
+        ReadExpect<'a, WizardPowers>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, range_bonuses, equipped, powers) = data;
 
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
             if viewshed.dirty {
                 viewshed.dirty = false;
-                viewshed.visible_tiles =
-                    field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
+                let mut range = viewshed.range;
+                for (bonus, equipped_by) in (&range_bonuses, &equipped).join() {
+                    if equipped_by.owner == ent {
+                        range += bonus.range;
+                    }
+                }
+                viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), range, &*map);
                 viewshed
                     .visible_tiles
                     .retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height);
@@ -35,6 +43,10 @@ impl<'a> System<'a> for VisibilitySystem {
                         map.revealed_tiles[idx] = true;
                         map.visible_tiles[idx] = true;
                     }
+                    if powers.see_all {
+                        map.revealed_tiles.iter_mut().for_each(|tile| *tile = true);
+                        map.visible_tiles.iter_mut().for_each(|tile| *tile = true);
+                    }
                 }
             }
         }
@@ -0,0 +1,386 @@
+//! Wizard mode, for testing: a console that can spawn things, move the player about, reveal the
+//! map and so on, opened with the backtick key once the game has been started with `--wizard`.
+//! Nothing done through it is recorded, so once it has changed a run, that run no longer writes
+//! a replay and doesn't go in the high-score table.
+
+use super::{
+    CombatStats, InBackpack, Item, Map, Position, State, Viewshed, backend,
+    keymap::{self, Action, Context},
+    map_indexing_system::MapIndexingSystem,
+    replay::Recording,
+    run_info::RunInfo,
+    saveload_system, spawner,
+    visibility_system::VisibilitySystem,
+};
+use rltk::{Point, RGB, Rltk, VirtualKeyCode};
+use specs::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// How many lines of output the console keeps.
+const HISTORY: usize = 30;
+/// How wide the console's lines are.
+const WIDTH: usize = 77;
+
+const HELP: &[&str] = &[
+    "spawn <name>      Spawns a monster or item next to you",
+    "give <name>       Puts an item in your backpack",
+    "names             Lists what can be spawned or given",
+    "teleport <x> <y>  Moves you to a tile",
+    "reveal            Reveals the whole level",
+    "depth <n>         Goes to a new level at depth n",
+    "heal              Restores your health",
+    "god               Toggles god mode: you take no damage",
+    "fov               Toggles seeing everything on the level",
+    "dump [<x> <y>]    Lists your components, or those of what's on a tile",
+];
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// The cheats that are switched on. These last until the world is replaced, by starting or
+/// loading a game.
+#[derive(Default)]
+pub struct WizardPowers {
+    pub god_mode: bool,
+    pub see_all: bool,
+}
+
+/// What's been typed into the console, and what it printed.
+#[derive(Default)]
+pub struct Console {
+    input: String,
+    output: Vec<String>,
+}
+
+/// Reruns the systems that keep the map up to date, after a command moves things about.
+fn refresh(ecs: &mut World) {
+    let mut map_index = MapIndexingSystem {};
+    map_index.run_now(ecs);
+    let mut vis = VisibilitySystem {};
+    vis.run_now(ecs);
+    ecs.maintain();
+}
+
+fn mark_player_moved(ecs: &mut World) {
+    let player_entity = *ecs.fetch::<Entity>();
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(player_entity) {
+        viewshed.dirty = true;
+    }
+}
+
+/// Marks the run as changed by the console, which can't be replayed or make the high scores.
+fn mark_wizardry(ecs: &mut World) {
+    ecs.write_resource::<RunInfo>().wizardry = true;
+    ecs.write_resource::<Recording>().complete = false;
+}
+
+/// The properly capitalised name for `name`, if it can be spawned.
+fn spawnable(name: &str) -> Result<&'static str, String> {
+    spawner::spawnable_names()
+        .find(|other| other.eq_ignore_ascii_case(name))
+        .ok_or(format!("Nothing called \"{}\" can be spawned.", name))
+}
+
+/// The nearest tile beside the player that's free for something to be spawned on.
+fn free_tile_near_player(ecs: &World) -> Option<Point> {
+    let player_pos = *ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    for (dx, dy) in [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+    ] {
+        let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+        if x < 0 || x >= map.width || y < 0 || y >= map.height {
+            continue;
+        }
+        let idx = map.xy_idx(x, y);
+        if !map.blocked[idx] && map.tile_content[idx].is_empty() {
+            return Some(Point::new(x, y));
+        }
+    }
+    None
+}
+
+fn spawn(ecs: &mut World, name: &str) -> Result<String, String> {
+    let name = spawnable(name)?;
+    let at = free_tile_near_player(ecs).ok_or("There's no room to spawn anything.")?;
+    spawner::spawn_named(ecs, name, at.x, at.y);
+    refresh(ecs);
+    Ok(format!("Spawned a {} at {}, {}.", name, at.x, at.y))
+}
+
+fn give(ecs: &mut World, name: &str) -> Result<String, String> {
+    let name = spawnable(name)?;
+    let player_entity = *ecs.fetch::<Entity>();
+    let item = spawner::spawn_named(ecs, name, 0, 0).ok_or("Unable to spawn it.")?;
+    if ecs.read_storage::<Item>().get(item).is_none() {
+        ecs.delete_entity(item).expect("Unable to delete entity");
+        return Err(format!("The {} isn't an item.", name));
+    }
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<InBackpack>()
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to insert backpack entry");
+    Ok(format!("Gave you a {}.", name))
+}
+
+fn teleport(ecs: &mut World, x: i32, y: i32) -> Result<String, String> {
+    {
+        let map = ecs.fetch::<Map>();
+        if x < 0 || x >= map.width || y < 0 || y >= map.height {
+            return Err(format!("{}, {} is off the map.", x, y));
+        }
+        if map.blocked[map.xy_idx(x, y)] {
+            return Err(format!("{}, {} is blocked.", x, y));
+        }
+    }
+    let player_entity = *ecs.fetch::<Entity>();
+    if let Some(pos) = ecs.write_storage::<Position>().get_mut(player_entity) {
+        pos.x = x;
+        pos.y = y;
+    }
+    *ecs.write_resource::<Point>() = Point::new(x, y);
+    mark_player_moved(ecs);
+    refresh(ecs);
+    Ok(format!("Teleported to {}, {}.", x, y))
+}
+
+fn dump(ecs: &mut World, at: Option<(i32, i32)>) -> Result<Vec<String>, String> {
+    let entities: Vec<Entity> = match at {
+        None => vec![*ecs.fetch::<Entity>()],
+        Some((x, y)) => {
+            let map = ecs.fetch::<Map>();
+            if x < 0 || x >= map.width || y < 0 || y >= map.height {
+                return Err(format!("{}, {} is off the map.", x, y));
+            }
+            map.tile_content[map.xy_idx(x, y)].clone()
+        }
+    };
+    if entities.is_empty() {
+        return Ok(vec!["There's nothing there.".to_string()]);
+    }
+    let mut lines = Vec::new();
+    for entity in entities {
+        lines.push(format!("Entity {}:", entity.id()));
+        let components = saveload_system::dump_entity(ecs, entity).map_err(|e| e.to_string())?;
+        lines.extend(components.into_iter().map(|line| format!("  {}", line)));
+    }
+    Ok(lines)
+}
+
+fn number(arg: Option<&str>) -> Result<i32, String> {
+    arg.and_then(|arg| arg.parse().ok())
+        .ok_or("Expected a number.".to_string())
+}
+
+/// Carries out one console command, returning what it printed.
+pub fn run_command(gs: &mut State, line: &str) -> Vec<String> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Vec::new();
+    };
+    let rest = words.clone().collect::<Vec<&str>>().join(" ");
+    let result: Result<Vec<String>, String> = match command {
+        "help" => Ok(HELP.iter().map(|line| line.to_string()).collect()),
+        "names" => Ok(vec![
+            spawner::spawnable_names().collect::<Vec<&str>>().join(", "),
+        ]),
+        "spawn" => spawn(&mut gs.ecs, &rest).map(|line| vec![line]),
+        "give" => give(&mut gs.ecs, &rest).map(|line| vec![line]),
+        "teleport" => number(words.next()).and_then(|x| {
+            let y = number(words.next())?;
+            teleport(&mut gs.ecs, x, y).map(|line| vec![line])
+        }),
+        "reveal" => {
+            let mut map = gs.ecs.fetch_mut::<Map>();
+            map.revealed_tiles.iter_mut().for_each(|tile| *tile = true);
+            Ok(vec!["Revealed the level.".to_string()])
+        }
+        "depth" => number(words.next()).and_then(|depth| {
+            if depth < 1 {
+                return Err("Depths start at 1.".to_string());
+            }
+            gs.ecs.fetch_mut::<Map>().depth = depth - 1;
+            gs.goto_next_level();
+            refresh(&mut gs.ecs);
+            Ok(vec![format!("Went to depth {}.", depth)])
+        }),
+        "heal" => {
+            let player_entity = *gs.ecs.fetch::<Entity>();
+            if let Some(stats) = gs.ecs.write_storage::<CombatStats>().get_mut(player_entity) {
+                stats.hp = stats.max_hp;
+            }
+            Ok(vec!["Healed.".to_string()])
+        }
+        "god" => {
+            let mut powers = gs.ecs.fetch_mut::<WizardPowers>();
+            powers.god_mode = !powers.god_mode;
+            Ok(vec![format!(
+                "God mode {}.",
+                if powers.god_mode { "on" } else { "off" }
+            )])
+        }
+        "fov" => {
+            let see_all = {
+                let mut powers = gs.ecs.fetch_mut::<WizardPowers>();
+                powers.see_all = !powers.see_all;
+                powers.see_all
+            };
+            mark_player_moved(&mut gs.ecs);
+            refresh(&mut gs.ecs);
+            Ok(vec![format!(
+                "Field of view {}.",
+                if see_all { "off" } else { "on" }
+            )])
+        }
+        "dump" => match words.next() {
+            None => dump(&mut gs.ecs, None),
+            Some(x) => number(Some(x)).and_then(|x| {
+                let y = number(words.next())?;
+                dump(&mut gs.ecs, Some((x, y)))
+            }),
+        },
+        _ => Err(format!("Unknown command \"{}\"; try help.", command)),
+    };
+    if result.is_ok() && !matches!(command, "help" | "names" | "dump") {
+        mark_wizardry(&mut gs.ecs);
+    }
+    result.unwrap_or_else(|e| vec![e])
+}
+
+/// Draws the console over the top of the map and reads what's typed into it. Returns true
+/// once the player closes it.
+pub fn console(gs: &mut State, ctx: &mut Rltk) -> bool {
+    if !gs.ecs.has_value::<Console>() {
+        gs.ecs.insert(Console::default());
+    }
+    let pressed = keymap::pressed(ctx, Context::Menu);
+    if pressed == Some(Action::MenuBack) {
+        return true;
+    }
+    if pressed == Some(Action::MenuSelect) {
+        let line = std::mem::take(&mut gs.ecs.fetch_mut::<Console>().input);
+        let output = run_command(gs, &line);
+        let mut console = gs.ecs.fetch_mut::<Console>();
+        console.output.push(format!("> {}", line));
+        for line in output {
+            let chars: Vec<char> = line.chars().collect();
+            for chunk in chars.chunks(WIDTH) {
+                console.output.push(chunk.iter().collect());
+            }
+        }
+        let excess = console.output.len().saturating_sub(HISTORY);
+        console.output.drain(..excess);
+    } else if ctx.key == Some(VirtualKeyCode::Back) {
+        gs.ecs.fetch_mut::<Console>().input.pop();
+    } else if let Some(c) = keymap::typed_char(ctx) {
+        gs.ecs.fetch_mut::<Console>().input.push(c);
+    }
+
+    let console = gs.ecs.fetch::<Console>();
+    backend::set_layer(ctx, backend::TEXT_LAYER);
+    ctx.draw_box(
+        0,
+        0,
+        79,
+        HISTORY as i32 + 3,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        2,
+        0,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        format!(
+            " Wizard console: help lists commands, {} closes ",
+            keymap::describe(Action::MenuBack)
+        ),
+    );
+    for (i, line) in console.output.iter().enumerate() {
+        ctx.print_color(
+            1,
+            1 + i as i32,
+            RGB::named(rltk::GRAY),
+            RGB::named(rltk::BLACK),
+            line,
+        );
+    }
+    ctx.print_color(
+        1,
+        HISTORY as i32 + 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        format!("> {}_", console.input),
+    );
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Monster, new_world};
+
+    #[test]
+    fn commands_change_the_world() {
+        let mut gs = State { ecs: new_world(3) };
+        let player_entity = *gs.ecs.fetch::<Entity>();
+
+        run_command(&mut gs, "give health potion");
+        let carried = gs
+            .ecs
+            .read_storage::<InBackpack>()
+            .join()
+            .filter(|pack| pack.owner == player_entity)
+            .count();
+        assert_eq!(carried, 1);
+        assert_eq!(run_command(&mut gs, "give orc"), ["The Orc isn't an item."]);
+
+        let monsters = gs.ecs.read_storage::<Monster>().count();
+        run_command(&mut gs, "spawn Goblin");
+        assert_eq!(gs.ecs.read_storage::<Monster>().count(), monsters + 1);
+
+        run_command(&mut gs, "depth 4");
+        assert_eq!(gs.ecs.fetch::<Map>().depth, 4);
+
+        let dump = run_command(&mut gs, "dump");
+        assert!(
+            dump.iter().any(|line| line.starts_with("  Player:")),
+            "{:?}",
+            dump
+        );
+        assert_eq!(run_command(&mut gs, "teleport 0 0"), ["0, 0 is blocked."]);
+        assert!(run_command(&mut gs, "frobnicate")[0].starts_with("Unknown command"));
+    }
+
+    #[test]
+    fn changed_runs_are_neither_replayed_nor_scored() {
+        let mut gs = State { ecs: new_world(3) };
+        run_command(&mut gs, "help");
+        run_command(&mut gs, "dump");
+        assert!(!gs.ecs.fetch::<RunInfo>().wizardry);
+        assert!(gs.ecs.fetch::<Recording>().complete);
+
+        run_command(&mut gs, "heal");
+        assert!(gs.ecs.fetch::<RunInfo>().wizardry);
+        assert!(!gs.ecs.fetch::<Recording>().complete);
+    }
+}